@@ -1,18 +1,56 @@
 // inference_api.rs
-use crate::model::tokenizer::Tokenizer;
-use crate::model::GLOBAL_MODEL;
-use actix_web::{post, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{App, HttpResponse, HttpServer, Responder, post, web};
+use serde::Deserialize;
+
+use crate::inference::{GenerationConfig, InferenceEngine};
+use crate::model::transformer::SimpleTransformer;
+use crate::sampler::SamplingConfig;
+use crate::tokenizer::Tokenizer;
+
+/// Body of a `POST /infer` request
+#[derive(Debug, Deserialize)]
+pub struct InferRequest {
+    pub prompt: String,
+    /// Defaults to `GenerationConfig::default().max_new_tokens`
+    pub max_new_tokens: Option<usize>,
+    /// Sampling settings; greedy decoding when omitted
+    #[serde(default)]
+    pub sampling: SamplingConfig,
+}
 
 #[post("/infer")]
-async fn infer_api(req_body: String) -> impl Responder {
-    let tokens = simple_tokenizer(&req_body);
-    let prediction = GLOBAL_MODEL.lock().unwrap().forward(&tokens);
-    HttpResponse::Ok().json(prediction)
+async fn infer_api(
+    engine: web::Data<InferenceEngine>,
+    req: web::Json<InferRequest>,
+) -> impl Responder {
+    let mut sampler = match req.sampling.build() {
+        Ok(sampler) => sampler,
+        Err(err) => return HttpResponse::BadRequest().body(err.to_string()),
+    };
+    let mut config = GenerationConfig::default();
+    if let Some(max_new_tokens) = req.max_new_tokens {
+        config.max_new_tokens = max_new_tokens;
+    }
+
+    // Generation is CPU-bound, so it runs on the blocking pool instead of
+    // stalling the async worker and every request queued on it
+    let generation = web::block(move || engine.generate(&req.prompt, &config, &mut sampler)).await;
+    match generation {
+        Ok(Ok(generation)) => HttpResponse::Ok().json(generation),
+        Ok(Err(err)) => HttpResponse::BadRequest().body(err.to_string()),
+        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    }
 }
 
 pub fn run_inference_server() -> std::io::Result<()> {
-    HttpServer::new(|| App::new().service(infer_api))
-        .bind("127.0.0.1:8080")?
-        .run()
-        .map_err(|e| e.into())
+    // The engine is read-only during inference, so requests share it without locking
+    let engine = web::Data::new(InferenceEngine::new(
+        SimpleTransformer::new(),
+        Tokenizer::new(),
+    ));
+    actix_web::rt::System::new().block_on(
+        HttpServer::new(move || App::new().app_data(engine.clone()).service(infer_api))
+            .bind("127.0.0.1:8080")?
+            .run(),
+    )
 }
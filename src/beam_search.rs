@@ -0,0 +1,225 @@
+use crate::model::kv_cache::KvCache;
+use crate::model::layers::positional::SequenceTooLong;
+use crate::model::transformer::SimpleTransformer;
+use crate::tokenizer::EOS_TOKEN_ID;
+use crate::training::loss::log_softmax;
+
+/// Settings of `beam_search`
+#[derive(Debug, Clone)]
+pub struct BeamSearchConfig {
+    /// Number of hypotheses kept alive at every step
+    pub beam_width: usize,
+    /// Upper bound on the number of generated tokens
+    pub max_new_tokens: usize,
+    /// Hypotheses are ranked by `log_prob / length^length_penalty`; values
+    /// above 1 favour longer outputs, below 1 shorter ones and 0 disables it
+    pub length_penalty: f32,
+    /// Stop as soon as `beam_width` hypotheses have finished, instead of
+    /// waiting until no live beam can outscore them
+    pub early_stopping: bool,
+    /// Number of best hypotheses returned, at most `beam_width`
+    pub num_return_sequences: usize,
+    /// A hypothesis finishes once it produces this token
+    pub eos_token_id: Option<usize>,
+}
+
+impl Default for BeamSearchConfig {
+    fn default() -> Self {
+        Self {
+            beam_width: 4,
+            max_new_tokens: 32,
+            length_penalty: 1.0,
+            early_stopping: false,
+            num_return_sequences: 1,
+            eos_token_id: Some(EOS_TOKEN_ID),
+        }
+    }
+}
+
+/// A finished beam search result
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hypothesis {
+    /// Generated token IDs, excluding the prompt and the end-of-sequence token
+    pub token_ids: Vec<usize>,
+    /// Sum of the log-probabilities of the generated tokens, including the
+    /// end-of-sequence token when it ended the hypothesis
+    pub log_prob: f32,
+    /// `log_prob` normalized by length, used for ranking
+    pub score: f32,
+}
+
+/// A live hypothesis with the KV cache of its sequence
+struct Beam {
+    token_ids: Vec<usize>,
+    log_prob: f32,
+    kv_cache: KvCache,
+    /// Log-probabilities of the token following `token_ids`
+    next_log_probs: Vec<f32>,
+}
+
+/// Beam search decoding of a continuation of `prompt_ids`, returning up to
+/// `num_return_sequences` hypotheses, best first
+///
+/// For causal models every beam keeps its own KV cache, so each step only
+/// runs the newly chosen token through the model; a beam's cache is moved
+/// to its last surviving continuation and copied for the others. An empty
+/// prompt, or one filling the model's `max_seq_len`, yields no hypotheses;
+/// a longer one is an error.
+pub fn beam_search(
+    model: &SimpleTransformer,
+    prompt_ids: &[usize],
+    config: &BeamSearchConfig,
+) -> Result<Vec<Hypothesis>, SequenceTooLong> {
+    let width = config.beam_width;
+    assert!(width > 0, "beam_width must be greater than zero");
+    model.check_input_len(prompt_ids.len())?;
+    let max_steps = match model.max_seq_len() {
+        Some(max) => config
+            .max_new_tokens
+            .min(max.saturating_sub(prompt_ids.len())),
+        None => config.max_new_tokens,
+    };
+    if prompt_ids.is_empty() || max_steps == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut kv_cache = model.new_kv_cache();
+    let next_log_probs = log_softmax(&model.next_token_logits(prompt_ids, &mut kv_cache));
+    let mut beams = vec![Beam {
+        token_ids: Vec::new(),
+        log_prob: 0.0,
+        kv_cache,
+        next_log_probs,
+    }];
+    let mut finished: Vec<Hypothesis> = Vec::new();
+
+    for step in 1..=max_steps {
+        // The best continuations of every beam; 2·width per beam guarantees
+        // `width` live candidates even if some of them end the sequence
+        let mut candidates: Vec<(f32, usize, usize)> = Vec::new();
+        for (b, beam) in beams.iter().enumerate() {
+            candidates.extend(
+                top_tokens(&beam.next_log_probs, 2 * width)
+                    .into_iter()
+                    .map(|(token, lp)| (beam.log_prob + lp, b, token)),
+            );
+        }
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut selected = Vec::with_capacity(width);
+        for (rank, &(log_prob, b, token)) in candidates.iter().enumerate() {
+            if config.eos_token_id == Some(token) {
+                // Only an end of sequence that would have made the beam counts
+                if rank < width {
+                    finished.push(hypothesis(
+                        beams[b].token_ids.clone(),
+                        log_prob,
+                        step,
+                        config.length_penalty,
+                    ));
+                }
+            } else {
+                selected.push((log_prob, b, token));
+                if selected.len() == width {
+                    break;
+                }
+            }
+        }
+        if selected.is_empty() || is_done(&finished, &selected, step, config) {
+            break;
+        }
+        if step == max_steps {
+            // Out of steps: the survivors compete with the finished hypotheses
+            // without running their last token through the model
+            for (log_prob, b, token) in selected {
+                let mut token_ids = beams[b].token_ids.clone();
+                token_ids.push(token);
+                finished.push(hypothesis(token_ids, log_prob, step, config.length_penalty));
+            }
+            break;
+        }
+
+        let mut uses = vec![0; beams.len()];
+        for &(_, b, _) in &selected {
+            uses[b] += 1;
+        }
+        let mut parents: Vec<Option<Beam>> = beams.into_iter().map(Some).collect();
+        beams = selected
+            .into_iter()
+            .map(|(log_prob, b, token)| {
+                uses[b] -= 1;
+                let (mut token_ids, mut kv_cache) = if uses[b] == 0 {
+                    let parent = parents[b]
+                        .take()
+                        .expect("a beam is moved only to its last child");
+                    (parent.token_ids, parent.kv_cache)
+                } else {
+                    let parent = parents[b]
+                        .as_ref()
+                        .expect("a beam is moved only to its last child");
+                    (parent.token_ids.clone(), parent.kv_cache.clone())
+                };
+                token_ids.push(token);
+                let sequence = [prompt_ids, &token_ids].concat();
+                let next_log_probs =
+                    log_softmax(&model.next_token_logits(&sequence, &mut kv_cache));
+                Beam {
+                    token_ids,
+                    log_prob,
+                    kv_cache,
+                    next_log_probs,
+                }
+            })
+            .collect();
+    }
+
+    finished.sort_by(|a, b| b.score.total_cmp(&a.score));
+    finished.truncate(config.num_return_sequences.min(width));
+    Ok(finished)
+}
+
+/// Whether no live beam can still displace the `beam_width` best finished hypotheses
+fn is_done(
+    finished: &[Hypothesis],
+    selected: &[(f32, usize, usize)],
+    step: usize,
+    config: &BeamSearchConfig,
+) -> bool {
+    let width = config.beam_width;
+    if finished.len() < width {
+        return false;
+    }
+    if config.early_stopping {
+        return true;
+    }
+    let mut scores: Vec<f32> = finished.iter().map(|h| h.score).collect();
+    scores.sort_by(|a, b| b.total_cmp(a));
+    let worst_kept = scores[width - 1];
+    let best_live = selected[0].0 / length_norm(step, config.length_penalty);
+    best_live <= worst_kept
+}
+
+fn hypothesis(token_ids: Vec<usize>, log_prob: f32, len: usize, length_penalty: f32) -> Hypothesis {
+    Hypothesis {
+        token_ids,
+        log_prob,
+        score: log_prob / length_norm(len, length_penalty),
+    }
+}
+
+fn length_norm(len: usize, length_penalty: f32) -> f32 {
+    (len.max(1) as f32).powf(length_penalty)
+}
+
+/// The `k` highest-scoring possible `(token, log_prob)` pairs, best first
+fn top_tokens(log_probs: &[f32], k: usize) -> Vec<(usize, f32)> {
+    let mut ranked: Vec<(usize, f32)> = log_probs
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|&(_, lp)| lp > f32::NEG_INFINITY)
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.truncate(k);
+    ranked
+}
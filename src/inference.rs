@@ -1,4 +1,38 @@
-use crate::{model::transformer::SimpleTransformer, tokenizer::Tokenizer};
+use serde::Serialize;
+
+use crate::{
+    beam_search::{BeamSearchConfig, beam_search},
+    model::{layers::positional::SequenceTooLong, module::Module, transformer::SimpleTransformer},
+    sampler::Sampler,
+    tokenizer::{EOS_TOKEN_ID, Tokenizer},
+};
+
+/// Stopping criteria for `InferenceEngine::generate`
+#[derive(Debug, Clone)]
+pub struct GenerationConfig {
+    /// Upper bound on the number of generated tokens
+    pub max_new_tokens: usize,
+    /// Generation stops once this token is produced; it is not returned
+    pub eos_token_id: Option<usize>,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            max_new_tokens: 32,
+            eos_token_id: Some(EOS_TOKEN_ID),
+        }
+    }
+}
+
+/// Continuation produced for a prompt
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Generation {
+    /// Decoded text of `token_ids`
+    pub text: String,
+    /// Generated token IDs, excluding the prompt and the end-of-sequence token
+    pub token_ids: Vec<usize>,
+}
 
 pub struct InferenceEngine {
     model: SimpleTransformer,
@@ -6,13 +40,83 @@ pub struct InferenceEngine {
 }
 
 impl InferenceEngine {
-    pub fn new(model: SimpleTransformer, tokenizer: Tokenizer) -> Self {
+    /// Wraps `model` for inference, switching it to eval mode so dropout
+    /// is disabled and outputs are deterministic
+    pub fn new(mut model: SimpleTransformer, tokenizer: Tokenizer) -> Self {
+        model.eval();
         Self { model, tokenizer }
     }
 
-    pub fn generate(&self, prompt: &str) -> Vec<f32> {
-        let tokens = self.tokenizer.tokenize(prompt);
-        let input = tokens.iter().map(|&t| t as f32).collect::<Vec<_>>();
-        self.model.forward(input)
+    /// Tokenizes `prompt` and extends it with tokens drawn by `sampler` until
+    /// `config` says to stop; fails if the prompt exceeds the model's `max_seq_len`
+    pub fn generate(
+        &self,
+        prompt: &str,
+        config: &GenerationConfig,
+        sampler: &mut Sampler,
+    ) -> Result<Generation, SequenceTooLong> {
+        let prompt_ids = self.tokenizer.tokenize(prompt);
+        let token_ids = self.generate_ids(&prompt_ids, config, sampler)?;
+        Ok(Generation {
+            text: self.tokenizer.decode(&token_ids),
+            token_ids,
+        })
+    }
+
+    /// Beam search continuations of `prompt`, best first; suited to tasks
+    /// like translation where the most likely output matters more than variety
+    pub fn generate_beams(
+        &self,
+        prompt: &str,
+        config: &BeamSearchConfig,
+    ) -> Result<Vec<Generation>, SequenceTooLong> {
+        let prompt_ids = self.tokenizer.tokenize(prompt);
+        let hypotheses = beam_search(&self.model, &prompt_ids, config)?;
+        Ok(hypotheses
+            .into_iter()
+            .map(|hypothesis| Generation {
+                text: self.tokenizer.decode(&hypothesis.token_ids),
+                token_ids: hypothesis.token_ids,
+            })
+            .collect())
+    }
+
+    /// Autoregressive loop over token IDs: samples the next token from the
+    /// last position's logits, appends it and repeats until `max_new_tokens`, the end-of-sequence
+    /// token or the model's `max_seq_len` is reached
+    ///
+    /// Causal models only run each new token through the network, reusing the
+    /// keys and values of earlier positions from a KV cache. `<pad>` is never
+    /// sampled (see `SimpleTransformer::next_token_logits`). An empty prompt
+    /// generates nothing; one longer than `max_seq_len` is an error.
+    pub fn generate_ids(
+        &self,
+        prompt_ids: &[usize],
+        config: &GenerationConfig,
+        sampler: &mut Sampler,
+    ) -> Result<Vec<usize>, SequenceTooLong> {
+        self.model.check_input_len(prompt_ids.len())?;
+        let mut sequence = prompt_ids.to_vec();
+        let mut generated = Vec::new();
+        let mut kv_cache = self.model.new_kv_cache();
+
+        while !sequence.is_empty() && generated.len() < config.max_new_tokens {
+            if self
+                .model
+                .max_seq_len()
+                .is_some_and(|max| sequence.len() >= max)
+            {
+                break;
+            }
+
+            let logits = self.model.next_token_logits(&sequence, &mut kv_cache);
+            let next = sampler.sample(&logits);
+            if config.eos_token_id == Some(next) {
+                break;
+            }
+            sequence.push(next);
+            generated.push(next);
+        }
+        Ok(generated)
     }
 }
@@ -1,7 +1,9 @@
 pub mod api;
+pub mod beam_search;
 pub mod config;
 pub mod inference;
 pub mod model;
+pub mod sampler;
 pub mod serialization;
 pub mod tokenizer;
 pub mod training;
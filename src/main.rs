@@ -1,9 +1,4 @@
-mod inference;
-mod model;
-mod training;
-mod utils;
-
-use crate::api::inference::run_inference_server;
+use llm_engine::api::inference::run_inference_server;
 
 fn main() {
     println!("Starting inference server...");
@@ -0,0 +1,225 @@
+use rand::Rng;
+
+use crate::model::common::Param;
+use crate::model::config::{ModelConfig, PositionalEncodingKind};
+use crate::model::init::Init;
+use crate::model::kv_cache::LayerKvCache;
+use crate::model::layers::{
+    attention::{AttentionCache, MultiHeadAttention},
+    dropout::Dropout,
+    feedforward::{FeedForward, FeedForwardCache},
+    norm::Norm,
+    rotary::RotaryEmbedding,
+};
+use crate::model::mask::AttentionMask;
+use crate::model::module::{Module, join};
+use crate::model::tensor::Tensor;
+
+/// One transformer layer: an attention sublayer followed by a feed-forward
+/// sublayer, each wrapped in a residual connection
+///
+/// With `pre_layer_norm` the norms are applied to each sublayer input
+/// (`x + f(norm(x))`, Pre-LN); otherwise they are applied after the residual
+/// addition (`norm(x + f(x))`, Post-LN).
+pub struct TransformerBlock {
+    pub attn: MultiHeadAttention,
+    pub attn_norm: Norm,
+    pub ff: FeedForward,
+    pub ff_norm: Norm,
+    /// Dropout on each sublayer output before it joins the residual stream
+    pub residual_dropout: Dropout,
+    pub pre_layer_norm: bool,
+}
+
+/// Intermediate values of one block forward pass needed for backward
+pub struct BlockCache {
+    pub attn: AttentionCache,
+    /// Input of `attn_norm`
+    pub attn_norm_input: Tensor,
+    pub ff: FeedForwardCache,
+    /// Input of `ff_norm`
+    pub ff_norm_input: Tensor,
+    /// Residual dropout masks of the attention and feed-forward outputs
+    pub attn_dropout_mask: Option<Tensor>,
+    pub ff_dropout_mask: Option<Tensor>,
+}
+
+impl TransformerBlock {
+    /// Builds a block sized by `config`; `rng` seeds its dropout layers
+    pub fn new<R: Rng + ?Sized>(config: &ModelConfig, rng: &mut R) -> Self {
+        let d_model = config.d_model;
+        let mut attn = MultiHeadAttention::new(d_model, config.n_heads)
+            .with_kv_heads(config.n_kv_heads.unwrap_or(config.n_heads))
+            .with_dropout(config.dropout, rng);
+        match config.positional_encoding {
+            PositionalEncodingKind::Absolute => {}
+            PositionalEncodingKind::Rotary { base, fraction } => {
+                let head_dim = d_model / config.n_heads;
+                attn = attn.with_rotary(RotaryEmbedding::new(
+                    head_dim,
+                    config.max_seq_len,
+                    base,
+                    fraction,
+                ));
+            }
+            PositionalEncodingKind::Alibi => attn = attn.with_alibi(),
+        }
+        Self {
+            attn,
+            attn_norm: Norm::new(config.norm, d_model),
+            ff: FeedForward::new(d_model, config.ff_hidden_size, config.activation),
+            ff_norm: Norm::new(config.norm, d_model),
+            residual_dropout: Dropout::new(config.dropout, rng),
+            pre_layer_norm: config.pre_layer_norm,
+        }
+    }
+
+    /// Draws all sublayer weights from `init` and resets the norms
+    ///
+    /// The projections writing into the residual stream (`attn.out_proj` and
+    /// `ff.down_proj`) are additionally multiplied by `residual_scale`, e.g.
+    /// `1/√(2·n_layers)` for GPT-2 style init.
+    pub fn init_weights<R: Rng + ?Sized>(&mut self, init: Init, residual_scale: f32, rng: &mut R) {
+        self.attn.init_weights(init, rng);
+        self.ff.init_weights(init, rng);
+        for weight in [
+            &mut self.attn.out_proj.weight,
+            &mut self.ff.down_proj.weight,
+        ] {
+            weight.value.iter_mut().for_each(|w| *w *= residual_scale);
+        }
+        self.attn_norm.reset_parameters();
+        self.ff_norm.reset_parameters();
+    }
+
+    /// Forward pass over `[seq_len][d_model]` or a batch `[batch][seq_len][d_model]`
+    pub fn forward(&self, x: &Tensor, mask: Option<&AttentionMask>) -> Tensor {
+        self.forward_with_cache(x, mask).0
+    }
+
+    /// Forward pass that also returns the values needed by `backward`
+    pub fn forward_with_cache(
+        &self,
+        x: &Tensor,
+        mask: Option<&AttentionMask>,
+    ) -> (Tensor, BlockCache) {
+        if self.pre_layer_norm {
+            // h = x + attn(norm(x)); y = h + ff(norm(h))
+            let (attn_out, attn) = self
+                .attn
+                .forward_with_cache(&self.attn_norm.forward(x), mask);
+            let (attn_out, attn_dropout_mask) = self.residual_dropout.forward(attn_out);
+            let h = x + &attn_out;
+
+            let (ff_out, ff) = self.ff.forward_with_cache(&self.ff_norm.forward(&h));
+            let (ff_out, ff_dropout_mask) = self.residual_dropout.forward(ff_out);
+            let y = &h + &ff_out;
+
+            let cache = BlockCache {
+                attn,
+                attn_norm_input: x.clone(),
+                ff,
+                ff_norm_input: h,
+                attn_dropout_mask,
+                ff_dropout_mask,
+            };
+            (y, cache)
+        } else {
+            // h = norm(x + attn(x)); y = norm(h + ff(h))
+            let (attn_out, attn) = self.attn.forward_with_cache(x, mask);
+            let (attn_out, attn_dropout_mask) = self.residual_dropout.forward(attn_out);
+            let attn_sum = x + &attn_out;
+            let h = self.attn_norm.forward(&attn_sum);
+
+            let (ff_out, ff) = self.ff.forward_with_cache(&h);
+            let (ff_out, ff_dropout_mask) = self.residual_dropout.forward(ff_out);
+            let ff_sum = &h + &ff_out;
+            let y = self.ff_norm.forward(&ff_sum);
+
+            let cache = BlockCache {
+                attn,
+                attn_norm_input: attn_sum,
+                ff,
+                ff_norm_input: ff_sum,
+                attn_dropout_mask,
+                ff_dropout_mask,
+            };
+            (y, cache)
+        }
+    }
+
+    /// Incremental forward pass over the new positions `x` `[n][d_model]` of a
+    /// sequence whose earlier keys and values are in `kv_cache`, which is extended
+    pub fn forward_incremental(
+        &self,
+        x: &Tensor,
+        kv_cache: &mut LayerKvCache,
+        mask: Option<&AttentionMask>,
+    ) -> Tensor {
+        if self.pre_layer_norm {
+            let attn_out =
+                self.attn
+                    .forward_incremental(&self.attn_norm.forward(x), kv_cache, mask);
+            let h = x + &self.residual_dropout.forward(attn_out).0;
+            let ff_out = self.ff.forward(&self.ff_norm.forward(&h));
+            &h + &self.residual_dropout.forward(ff_out).0
+        } else {
+            let attn_out = self.attn.forward_incremental(x, kv_cache, mask);
+            let h = self
+                .attn_norm
+                .forward(&(x + &self.residual_dropout.forward(attn_out).0));
+            let ff_out = self.ff.forward(&h);
+            self.ff_norm
+                .forward(&(&h + &self.residual_dropout.forward(ff_out).0))
+        }
+    }
+
+    /// Backward pass: accumulates gradients of every sublayer and returns the
+    /// gradient with respect to the block input
+    pub fn backward(&mut self, cache: &BlockCache, grad_output: &Tensor) -> Tensor {
+        let attn_mask = cache.attn_dropout_mask.as_ref();
+        let ff_mask = cache.ff_dropout_mask.as_ref();
+        if self.pre_layer_norm {
+            let grad_ff_out = Dropout::backward(ff_mask, grad_output.clone());
+            let grad_normed = self.ff.backward(&cache.ff, &grad_ff_out);
+            let grad_h = grad_output + &self.ff_norm.backward(&cache.ff_norm_input, &grad_normed);
+
+            let grad_attn_out = Dropout::backward(attn_mask, grad_h.clone());
+            let grad_normed = self.attn.backward(&cache.attn, &grad_attn_out);
+            &grad_h
+                + &self
+                    .attn_norm
+                    .backward(&cache.attn_norm_input, &grad_normed)
+        } else {
+            let grad_sum = self.ff_norm.backward(&cache.ff_norm_input, grad_output);
+            let grad_ff_out = Dropout::backward(ff_mask, grad_sum.clone());
+            let grad_h = &grad_sum + &self.ff.backward(&cache.ff, &grad_ff_out);
+
+            let grad_sum = self.attn_norm.backward(&cache.attn_norm_input, &grad_h);
+            let grad_attn_out = Dropout::backward(attn_mask, grad_sum.clone());
+            &grad_sum + &self.attn.backward(&cache.attn, &grad_attn_out)
+        }
+    }
+}
+
+impl Module for TransformerBlock {
+    fn set_training(&mut self, training: bool) {
+        self.attn.set_training(training);
+        self.residual_dropout.set_training(training);
+    }
+
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        self.attn.visit_params(&join(prefix, "attn"), f);
+        self.attn_norm.visit_params(&join(prefix, "attn_norm"), f);
+        self.ff.visit_params(&join(prefix, "ff"), f);
+        self.ff_norm.visit_params(&join(prefix, "ff_norm"), f);
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        self.attn.visit_params_mut(&join(prefix, "attn"), f);
+        self.attn_norm
+            .visit_params_mut(&join(prefix, "attn_norm"), f);
+        self.ff.visit_params_mut(&join(prefix, "ff"), f);
+        self.ff_norm.visit_params_mut(&join(prefix, "ff_norm"), f);
+    }
+}
@@ -6,13 +6,21 @@ pub struct Param {
 }
 
 impl Param {
+    /// Creates a zero-initialized parameter; layers draw their actual
+    /// starting values from an `Init` scheme
     pub fn new(size: usize) -> Self {
         Self {
-            value: vec![0.01; size], // init small weights
+            value: vec![0.0; size],
             grad: vec![0.0; size],
         }
     }
 
+    /// Creates a parameter with the given initial values and zero gradient
+    pub fn from_values(value: Vec<f32>) -> Self {
+        let grad = vec![0.0; value.len()];
+        Self { value, grad }
+    }
+
     pub fn zero_grad(&mut self) {
         self.grad.iter_mut().for_each(|g| *g = 0.0);
     }
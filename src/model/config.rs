@@ -1,3 +1,29 @@
+use std::fmt;
+
+use crate::model::init::Init;
+use crate::model::layers::activation::Activation;
+use crate::model::layers::norm::NormKind;
+use crate::model::layers::rotary::rotary_dim;
+
+/// Position encoding scheme of a transformer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PositionalEncodingKind {
+    /// Position vectors added to the token embeddings, sinusoidal or learned
+    /// per `learned_positional_encoding`
+    Absolute,
+    /// Rotary embeddings (RoPE) applied to queries and keys in every layer;
+    /// LLaMA uses `base = 10000.0` and `fraction = 1.0`
+    Rotary {
+        /// Base of the rotation frequencies
+        base: f32,
+        /// Share of each head's features that are rotated, in `(0, 1]`
+        fraction: f32,
+    },
+    /// Per-head linear distance biases on the attention scores (ALiBi); no
+    /// position table, so inputs may exceed `max_seq_len`
+    Alibi,
+}
+
 /// Configuration struct for transformer model
 #[derive(Debug, Clone)]
 pub struct ModelConfig {
@@ -5,24 +31,40 @@ pub struct ModelConfig {
     pub d_model: usize,
     /// Number of attention heads
     pub n_heads: usize,
+    /// Number of key/value heads shared by the query heads (grouped-query
+    /// attention); `None` uses `n_heads`, `Some(1)` is multi-query attention
+    pub n_kv_heads: Option<usize>,
     /// Number of transformer layers
     pub n_layers: usize,
-    /// Maximum sequence length
+    /// Maximum sequence length; not a limit with ALiBi
     pub max_seq_len: usize,
     /// Vocabulary size
     pub vocab_size: usize,
     /// Feedforward hidden layer size
     pub ff_hidden_size: usize,
+    /// Nonlinearity used in the feedforward block
+    pub activation: Activation,
     /// Dropout rate for regularization
     pub dropout: f32,
     /// Whether to use weight sharing between input/output embeddings
     pub weight_sharing: bool,
     /// Whether to use layer normalization before residual connections (Pre-LN)
     pub pre_layer_norm: bool,
-    /// Whether to tie positional encoding with learned embeddings
+    /// Normalization used in every block and before the output head
+    pub norm: NormKind,
+    /// How token positions are made visible to attention
+    pub positional_encoding: PositionalEncodingKind,
+    /// Whether absolute positions use a trainable embedding table instead of fixed sinusoids
     pub learned_positional_encoding: bool,
-    /// Whether to use quantized linear layers
+    /// Whether to use quantized linear layers; not supported yet, so
+    /// `validate` rejects `true`
     pub use_quantization: bool,
+    /// Whether attention is restricted to earlier positions (decoder-style)
+    pub causal: bool,
+    /// Scheme used for embedding and projection weights
+    pub init: Init,
+    /// Scales residual output projections by `1/√(2·n_layers)` (GPT-2 init)
+    pub scaled_residual_init: bool,
     /// Random seed for reproducibility
     pub seed: Option<u64>,
 }
@@ -32,16 +74,143 @@ impl Default for ModelConfig {
         Self {
             d_model: 128,
             n_heads: 8,
+            n_kv_heads: None,
             n_layers: 4,
             max_seq_len: 128,
             vocab_size: 10000,
             ff_hidden_size: 512,
+            activation: Activation::Gelu,
             dropout: 0.1,
             weight_sharing: false,
             pre_layer_norm: true,
+            norm: NormKind::LayerNorm,
+            positional_encoding: PositionalEncodingKind::Absolute,
             learned_positional_encoding: false,
             use_quantization: false,
+            causal: true,
+            init: Init::default(),
+            scaled_residual_init: true,
             seed: Some(42),
         }
     }
 }
+
+impl ModelConfig {
+    /// Checks that the configuration describes a buildable model
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (field, value) in [
+            ("d_model", self.d_model),
+            ("n_heads", self.n_heads),
+            ("n_layers", self.n_layers),
+            ("max_seq_len", self.max_seq_len),
+            ("vocab_size", self.vocab_size),
+            ("ff_hidden_size", self.ff_hidden_size),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::Zero { field });
+            }
+        }
+        if !self.d_model.is_multiple_of(self.n_heads) {
+            return Err(ConfigError::HeadsNotDivisible {
+                d_model: self.d_model,
+                n_heads: self.n_heads,
+            });
+        }
+        if let Some(n_kv_heads) = self.n_kv_heads {
+            if n_kv_heads == 0 {
+                return Err(ConfigError::Zero {
+                    field: "n_kv_heads",
+                });
+            }
+            if !self.n_heads.is_multiple_of(n_kv_heads) {
+                return Err(ConfigError::KvHeadsNotDivisible {
+                    n_heads: self.n_heads,
+                    n_kv_heads,
+                });
+            }
+        }
+        if self.use_quantization {
+            return Err(ConfigError::QuantizationUnsupported);
+        }
+        if !(0.0..1.0).contains(&self.dropout) {
+            return Err(ConfigError::InvalidDropout(self.dropout));
+        }
+        if let Init::Normal { std } = self.init
+            && !(std.is_finite() && std >= 0.0)
+        {
+            return Err(ConfigError::InvalidInitStd(std));
+        }
+        if let PositionalEncodingKind::Rotary { base, fraction } = self.positional_encoding {
+            if !(base.is_finite() && base > 1.0) {
+                return Err(ConfigError::InvalidRopeBase(base));
+            }
+            let head_dim = self.d_model / self.n_heads;
+            if !(fraction > 0.0 && fraction <= 1.0) || rotary_dim(head_dim, fraction) < 2 {
+                return Err(ConfigError::InvalidRotaryFraction { fraction, head_dim });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reasons a `ModelConfig` cannot be turned into a model
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// A size field that must be positive was zero
+    Zero { field: &'static str },
+    /// `d_model` cannot be split evenly across attention heads
+    HeadsNotDivisible { d_model: usize, n_heads: usize },
+    /// Query heads cannot be split evenly across key/value heads
+    KvHeadsNotDivisible { n_heads: usize, n_kv_heads: usize },
+    /// Dropout probability outside `[0, 1)`
+    InvalidDropout(f32),
+    /// Standard deviation of `Init::Normal` negative or not finite
+    InvalidInitStd(f32),
+    /// RoPE base frequency not a finite number above 1
+    InvalidRopeBase(f32),
+    /// RoPE fraction outside `(0, 1]` or too small to rotate a feature pair
+    InvalidRotaryFraction { fraction: f32, head_dim: usize },
+    /// `use_quantization` was set, but quantized layers are not implemented
+    QuantizationUnsupported,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Zero { field } => write!(f, "`{field}` must be greater than zero"),
+            ConfigError::HeadsNotDivisible { d_model, n_heads } => write!(
+                f,
+                "`d_model` ({d_model}) must be divisible by `n_heads` ({n_heads})"
+            ),
+            ConfigError::KvHeadsNotDivisible {
+                n_heads,
+                n_kv_heads,
+            } => write!(
+                f,
+                "`n_heads` ({n_heads}) must be divisible by `n_kv_heads` ({n_kv_heads})"
+            ),
+            ConfigError::InvalidDropout(p) => {
+                write!(f, "`dropout` must be in [0, 1), got {p}")
+            }
+            ConfigError::InvalidInitStd(std) => {
+                write!(
+                    f,
+                    "`init` standard deviation must be finite and >= 0, got {std}"
+                )
+            }
+            ConfigError::InvalidRopeBase(base) => {
+                write!(f, "rotary `base` must be finite and > 1, got {base}")
+            }
+            ConfigError::InvalidRotaryFraction { fraction, head_dim } => write!(
+                f,
+                "rotary `fraction` must be in (0, 1] and cover at least two of the \
+                 {head_dim} head features, got {fraction}"
+            ),
+            ConfigError::QuantizationUnsupported => {
+                write!(f, "`use_quantization` is not supported yet")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -1,4 +1,5 @@
 pub const HIDDEN_SIZE: usize = 64;
 pub const MAX_SEQ_LEN: usize = 128;
+pub const NUM_HEADS: usize = 8;
 pub const NUM_LAYERS: usize = 2;
 pub const VOCAB_SIZE: usize = 256; // You can change this based on your tokenizer
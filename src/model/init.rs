@@ -0,0 +1,76 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Weight initialization scheme
+///
+/// Fans follow the `[rows][cols]` layout of the weight: `fan_in` is the
+/// number of columns (inputs) and `fan_out` the number of rows (outputs).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Init {
+    /// All zeros, as used for biases and LayerNorm beta
+    Zeros,
+    /// Every entry set to the same value
+    Constant(f32),
+    /// `N(0, std²)`; GPT-2 uses `std = 0.02`
+    Normal { std: f32 },
+    /// Xavier/Glorot uniform: `U(-a, a)` with `a = √(6 / (fan_in + fan_out))`
+    XavierUniform,
+    /// Xavier/Glorot normal: `std = √(2 / (fan_in + fan_out))`
+    XavierNormal,
+    /// Kaiming/He uniform for ReLU-like activations: `a = √(6 / fan_in)`
+    KaimingUniform,
+    /// Kaiming/He normal for ReLU-like activations: `std = √(2 / fan_in)`
+    KaimingNormal,
+}
+
+impl Default for Init {
+    fn default() -> Self {
+        Init::Normal { std: 0.02 }
+    }
+}
+
+impl Init {
+    /// Overwrites `values` with samples drawn from this scheme
+    pub fn fill<R: Rng + ?Sized>(
+        &self,
+        values: &mut [f32],
+        fan_in: usize,
+        fan_out: usize,
+        rng: &mut R,
+    ) {
+        let (fan_in, fan_out) = (fan_in.max(1) as f32, fan_out.max(1) as f32);
+        match *self {
+            Init::Zeros => values.fill(0.0),
+            Init::Constant(c) => values.fill(c),
+            Init::Normal { std } => fill_normal(values, std, rng),
+            Init::XavierUniform => fill_uniform(values, (6.0 / (fan_in + fan_out)).sqrt(), rng),
+            Init::XavierNormal => fill_normal(values, (2.0 / (fan_in + fan_out)).sqrt(), rng),
+            Init::KaimingUniform => fill_uniform(values, (6.0 / fan_in).sqrt(), rng),
+            Init::KaimingNormal => fill_normal(values, (2.0 / fan_in).sqrt(), rng),
+        }
+    }
+}
+
+/// Deterministic RNG for `Some(seed)`, OS-seeded otherwise
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    }
+}
+
+fn fill_uniform<R: Rng + ?Sized>(values: &mut [f32], bound: f32, rng: &mut R) {
+    for v in values {
+        *v = rng.random_range(-bound..=bound);
+    }
+}
+
+/// Box-Muller sampling, so no extra distribution crate is needed
+fn fill_normal<R: Rng + ?Sized>(values: &mut [f32], std: f32, rng: &mut R) {
+    for v in values {
+        // 1 - U[0, 1) lies in (0, 1], keeping the logarithm finite
+        let u1: f32 = 1.0 - rng.random::<f32>();
+        let u2: f32 = rng.random();
+        *v = std * (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos();
+    }
+}
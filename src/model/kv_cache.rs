@@ -0,0 +1,103 @@
+use crate::model::tensor::TensorView;
+
+/// Keys and values one attention layer computed for the positions decoded so far
+#[derive(Debug, Clone)]
+pub struct LayerKvCache {
+    keys: Vec<f32>,
+    values: Vec<f32>,
+    /// Features per position: `n_kv_heads · head_dim`
+    kv_size: usize,
+}
+
+impl LayerKvCache {
+    pub fn new(kv_size: usize) -> Self {
+        Self {
+            keys: Vec::new(),
+            values: Vec::new(),
+            kv_size,
+        }
+    }
+
+    /// Number of cached positions
+    pub fn len(&self) -> usize {
+        self.keys.len() / self.kv_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Appends the keys and values `[n][kv_size]` of the next `n` positions
+    pub fn extend(&mut self, keys: &[f32], values: &[f32]) {
+        assert_eq!(keys.len(), values.len(), "keys and values must match");
+        assert!(
+            keys.len().is_multiple_of(self.kv_size),
+            "cached rows must hold {} features",
+            self.kv_size
+        );
+        self.keys.extend_from_slice(keys);
+        self.values.extend_from_slice(values);
+    }
+
+    /// Cached keys, shape `[len][kv_size]`
+    pub fn keys(&self) -> TensorView<'_> {
+        TensorView::new(&self.keys, &[self.len(), self.kv_size])
+    }
+
+    /// Cached values, shape `[len][kv_size]`
+    pub fn values(&self) -> TensorView<'_> {
+        TensorView::new(&self.values, &[self.len(), self.kv_size])
+    }
+
+    /// Keeps only the first `len` positions
+    pub fn truncate(&mut self, len: usize) {
+        self.keys.truncate(len * self.kv_size);
+        self.values.truncate(len * self.kv_size);
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.values.clear();
+    }
+}
+
+/// Key/value cache of one sequence across every transformer layer
+///
+/// Lets autoregressive decoding run only the new tokens through the model:
+/// earlier positions are read back from the cache instead of recomputed.
+#[derive(Debug, Clone)]
+pub struct KvCache {
+    pub layers: Vec<LayerKvCache>,
+}
+
+impl KvCache {
+    /// Empty cache for `n_layers` layers storing `kv_size` features per position
+    pub fn new(n_layers: usize, kv_size: usize) -> Self {
+        Self {
+            layers: (0..n_layers).map(|_| LayerKvCache::new(kv_size)).collect(),
+        }
+    }
+
+    /// Number of positions already processed
+    pub fn len(&self) -> usize {
+        self.layers.first().map_or(0, LayerKvCache::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Rolls the sequence back to its first `len` positions
+    pub fn truncate(&mut self, len: usize) {
+        for layer in &mut self.layers {
+            layer.truncate(len);
+        }
+    }
+
+    /// Forgets every position so the cache can serve a new sequence
+    pub fn reset(&mut self) {
+        for layer in &mut self.layers {
+            layer.clear();
+        }
+    }
+}
@@ -0,0 +1,64 @@
+/// Nonlinearity used inside the feed-forward block
+///
+/// Gated variants (`SwiGlu`, `GeGlu`) multiply an activated gate projection
+/// with a linear up projection; `apply` returns the gate nonlinearity for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Activation {
+    Relu,
+    #[default]
+    Gelu,
+    Silu,
+    SwiGlu,
+    GeGlu,
+}
+
+impl Activation {
+    /// Whether the feed-forward block needs a separate gate projection
+    pub fn is_gated(self) -> bool {
+        matches!(self, Activation::SwiGlu | Activation::GeGlu)
+    }
+
+    /// Applies the scalar nonlinearity to `x`
+    pub fn apply(self, x: f32) -> f32 {
+        match self {
+            Activation::Relu => x.max(0.0),
+            Activation::Gelu | Activation::GeGlu => gelu(x),
+            Activation::Silu | Activation::SwiGlu => x * sigmoid(x),
+        }
+    }
+
+    /// Derivative of `apply` at `x`
+    pub fn derivative(self, x: f32) -> f32 {
+        match self {
+            Activation::Relu => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Activation::Gelu | Activation::GeGlu => gelu_derivative(x),
+            Activation::Silu | Activation::SwiGlu => {
+                let s = sigmoid(x);
+                s + x * s * (1.0 - s)
+            }
+        }
+    }
+}
+
+const SQRT_2_OVER_PI: f32 = 0.797_884_6;
+const GELU_COEFF: f32 = 0.044_715;
+
+/// GELU using the tanh approximation from GPT-2
+fn gelu(x: f32) -> f32 {
+    0.5 * x * (1.0 + (SQRT_2_OVER_PI * (x + GELU_COEFF * x.powi(3))).tanh())
+}
+
+fn gelu_derivative(x: f32) -> f32 {
+    let t = (SQRT_2_OVER_PI * (x + GELU_COEFF * x.powi(3))).tanh();
+    0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * SQRT_2_OVER_PI * (1.0 + 3.0 * GELU_COEFF * x * x)
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
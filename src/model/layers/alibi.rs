@@ -0,0 +1,56 @@
+use crate::model::tensor::Tensor;
+
+/// Attention with Linear Biases (ALiBi)
+///
+/// Instead of encoding positions in the inputs, every head `h` penalizes the
+/// score of a key `d` positions away from the query by `slopes[h] · d`. The
+/// bias depends only on distances, so sequences longer than those seen in
+/// training need no extra parameters.
+pub struct Alibi {
+    pub slopes: Vec<f32>,
+}
+
+impl Alibi {
+    pub fn new(n_heads: usize) -> Self {
+        Self {
+            slopes: alibi_slopes(n_heads),
+        }
+    }
+
+    /// Additive bias `[n_heads][query_len][key_len]`, where the queries are
+    /// the last `query_len` of the `key_len` positions
+    pub fn bias(&self, query_len: usize, key_len: usize) -> Tensor {
+        let offset = key_len - query_len;
+        let mut bias = Vec::with_capacity(self.slopes.len() * query_len * key_len);
+        for &slope in &self.slopes {
+            for i in 0..query_len {
+                let pos = (offset + i) as f32;
+                bias.extend((0..key_len).map(|j| -slope * (pos - j as f32).abs()));
+            }
+        }
+        Tensor::new(bias, &[self.slopes.len(), query_len, key_len])
+    }
+}
+
+/// Head slopes from the ALiBi paper: the geometric sequence `2^(-8/n)`,
+/// `2^(-16/n)`, ... for a power-of-two `n`; other head counts take the slopes
+/// of the next lower power of two, topped up with every other slope of twice that
+pub fn alibi_slopes(n_heads: usize) -> Vec<f32> {
+    fn geometric(n: usize) -> Vec<f32> {
+        let start = 2f32.powf(-8.0 / n as f32);
+        (1..=n).map(|i| start.powi(i as i32)).collect()
+    }
+
+    if n_heads == 0 {
+        return Vec::new();
+    }
+    let closest = 1 << n_heads.ilog2();
+    let mut slopes = geometric(closest);
+    slopes.extend(
+        geometric(2 * closest)
+            .into_iter()
+            .step_by(2)
+            .take(n_heads - closest),
+    );
+    slopes
+}
@@ -1,63 +1,370 @@
-use crate::model::layers::linear::Linear;
+use rand::Rng;
 
-/// A simplified multi-head attention block composed of projection layers
+use crate::model::{
+    common::Param,
+    init::Init,
+    kv_cache::LayerKvCache,
+    layers::{alibi::Alibi, dropout::Dropout, linear::Linear, rotary::RotaryEmbedding},
+    mask::AttentionMask,
+    module::{Module, join},
+    tensor::{Tensor, TensorView},
+};
+
+/// Multi-head scaled dot-product attention block composed of projection layers
 pub struct MultiHeadAttention {
+    pub n_heads: usize,
+    /// Key/value heads, each shared by `n_heads / n_kv_heads` query heads;
+    /// equal to `n_heads` for standard multi-head attention
+    pub n_kv_heads: usize,
+    pub head_dim: usize,
     pub query_proj: Linear,
     pub key_proj: Linear,
     pub value_proj: Linear,
     pub out_proj: Linear,
+    /// Dropout on the attention probabilities
+    pub dropout: Dropout,
+    /// Rotary position embedding applied to queries and keys, if any
+    pub rotary: Option<RotaryEmbedding>,
+    /// Per-head linear distance biases added to the scores, if any
+    pub alibi: Option<Alibi>,
+}
+
+/// Intermediate values of one attention forward pass needed for backward
+pub struct AttentionCache {
+    pub input: Tensor,
+    /// Queries `[.., seq_len][n_heads · head_dim]` and keys
+    /// `[.., seq_len][n_kv_heads · head_dim]`, after the rotary embedding when one is used
+    pub q: Tensor,
+    pub k: Tensor,
+    pub v: Tensor,
+    /// Attention probabilities before dropout, shape `[.., n_heads][seq_len][seq_len]`
+    pub probs: Tensor,
+    /// Scaled keep mask of the attention dropout, if it was active
+    pub dropout_mask: Option<Tensor>,
+    /// Concatenated head outputs before `out_proj`
+    pub context: Tensor,
 }
 
 impl MultiHeadAttention {
     /// Initialize a multi-head attention block
-    pub fn new(hidden_size: usize) -> Self {
+    ///
+    /// `hidden_size` must be divisible by `n_heads`; each head attends over
+    /// `hidden_size / n_heads` dimensions.
+    pub fn new(hidden_size: usize, n_heads: usize) -> Self {
+        assert!(
+            n_heads > 0 && hidden_size.is_multiple_of(n_heads),
+            "hidden_size ({hidden_size}) must be divisible by n_heads ({n_heads})"
+        );
         Self {
+            n_heads,
+            n_kv_heads: n_heads,
+            head_dim: hidden_size / n_heads,
             query_proj: Linear::new(hidden_size, hidden_size),
             key_proj: Linear::new(hidden_size, hidden_size),
             value_proj: Linear::new(hidden_size, hidden_size),
             out_proj: Linear::new(hidden_size, hidden_size),
+            dropout: Dropout::disabled(),
+            rotary: None,
+            alibi: None,
+        }
+    }
+
+    /// Shares each key/value head between `n_heads / n_kv_heads` query heads,
+    /// shrinking the key and value projections: grouped-query attention, or
+    /// multi-query attention for `n_kv_heads == 1`
+    pub fn with_kv_heads(mut self, n_kv_heads: usize) -> Self {
+        assert!(
+            n_kv_heads > 0 && self.n_heads.is_multiple_of(n_kv_heads),
+            "n_heads ({}) must be divisible by n_kv_heads ({n_kv_heads})",
+            self.n_heads
+        );
+        let hidden_size = self.n_heads * self.head_dim;
+        let kv_size = n_kv_heads * self.head_dim;
+        self.n_kv_heads = n_kv_heads;
+        self.key_proj = Linear::new(hidden_size, kv_size);
+        self.value_proj = Linear::new(hidden_size, kv_size);
+        self
+    }
+
+    /// Enables dropout with probability `p` on the attention probabilities
+    pub fn with_dropout<R: Rng + ?Sized>(mut self, p: f32, rng: &mut R) -> Self {
+        self.dropout = Dropout::new(p, rng);
+        self
+    }
+
+    /// Encodes positions by rotating queries and keys with `rotary`
+    pub fn with_rotary(mut self, rotary: RotaryEmbedding) -> Self {
+        assert_eq!(rotary.head_dim, self.head_dim, "rotary head_dim mismatch");
+        self.rotary = Some(rotary);
+        self
+    }
+
+    /// Encodes positions by ALiBi distance biases on the attention scores
+    pub fn with_alibi(mut self) -> Self {
+        self.alibi = Some(Alibi::new(self.n_heads));
+        self
+    }
+
+    /// Draws every projection's weights from `init`
+    pub fn init_weights<R: Rng + ?Sized>(&mut self, init: Init, rng: &mut R) {
+        self.query_proj.init_weights(init, rng);
+        self.key_proj.init_weights(init, rng);
+        self.value_proj.init_weights(init, rng);
+        self.out_proj.init_weights(init, rng);
+    }
+
+    /// Forward pass over a sequence `[seq_len][hidden_size]` or a batch of
+    /// sequences `[batch][seq_len][hidden_size]`
+    ///
+    /// Projects every position to Q/K/V (rotating Q and K when `rotary` is
+    /// set), splits them into `n_heads` query heads and `n_kv_heads` shared
+    /// key/value heads, computes `softmax(Q·Kᵀ / √head_dim)·V` per query head
+    /// and concatenates the heads back
+    /// through `out_proj`. ALiBi biases (when `alibi` is set) and an optional
+    /// additive `mask` (batched or not) are added to the scores of every head.
+    pub fn forward(&self, input: &Tensor, mask: Option<&AttentionMask>) -> Tensor {
+        self.forward_with_cache(input, mask).0
+    }
+
+    /// Forward pass that also returns the values needed by `backward`
+    pub fn forward_with_cache(
+        &self,
+        input: &Tensor,
+        mask: Option<&AttentionMask>,
+    ) -> (Tensor, AttentionCache) {
+        let (q, k, v) = self.project(input, 0);
+        let probs = self.probs(&q, k.view(), mask);
+        let (dropped, dropout_mask) = self.dropout.forward(probs.clone());
+        let context = self.context(dropped, v.view());
+        let output = self.out_proj.forward(&context);
+        let cache = AttentionCache {
+            input: input.clone(),
+            q,
+            k,
+            v,
+            probs,
+            dropout_mask,
+            context,
+        };
+        (output, cache)
+    }
+
+    /// Incremental forward pass for decoding: the new positions `input`
+    /// `[n][hidden_size]` attend over every position in `kv_cache` and
+    /// themselves, and their keys and values are appended to the cache
+    ///
+    /// `mask`, if given, has shape `[n][kv_cache.len() + n]`. Nothing is kept
+    /// for `backward`.
+    pub fn forward_incremental(
+        &self,
+        input: &Tensor,
+        kv_cache: &mut LayerKvCache,
+        mask: Option<&AttentionMask>,
+    ) -> Tensor {
+        assert_eq!(input.ndim(), 2, "a KV cache holds a single sequence");
+        let (q, k, v) = self.project(input, kv_cache.len());
+        kv_cache.extend(k.data(), v.data());
+        let probs = self.probs(&q, kv_cache.keys(), mask);
+        let (dropped, _) = self.dropout.forward(probs);
+        self.out_proj
+            .forward(&self.context(dropped, kv_cache.values()))
+    }
+
+    /// Backward pass: accumulates projection gradients and returns the
+    /// gradient with respect to the input sequence
+    ///
+    /// Masked positions have zero probability, so they receive no gradient
+    /// and the mask itself is not needed here. Key/value gradients are summed
+    /// over the query heads sharing them.
+    pub fn backward(&mut self, cache: &AttentionCache, grad_output: &Tensor) -> Tensor {
+        let group = self.group_size();
+        let grad_context = self.out_proj.backward(&cache.context, grad_output);
+        let grad_context = self.split_heads(grad_context.view(), group);
+
+        // context = dropout(P)·V
+        let dropped = match &cache.dropout_mask {
+            Some(mask) => &cache.probs * mask,
+            None => cache.probs.clone(),
+        };
+        let grad_dropped = grad_context.matmul(&self.split_heads(cache.v.view(), 1).t());
+        let grad_v = self.group(dropped).view().t().matmul(&grad_context);
+        let grad_probs = Dropout::backward(cache.dropout_mask.as_ref(), self.ungroup(grad_dropped));
+
+        // Softmax backward, then scores = scale · Q·Kᵀ
+        let mut grad_scores = grad_probs;
+        for (g, p) in grad_scores.rows_mut().zip(cache.probs.rows()) {
+            let weighted: f32 = p.iter().zip(g.iter()).map(|(a, b)| a * b).sum();
+            for (g, p) in g.iter_mut().zip(p) {
+                *g = p * (*g - weighted) * self.scale();
+            }
+        }
+        let grad_scores = self.group(grad_scores);
+        let grad_q = grad_scores
+            .view()
+            .matmul(&self.split_heads(cache.k.view(), 1));
+        let grad_k = grad_scores
+            .view()
+            .t()
+            .matmul(&self.split_heads(cache.q.view(), group));
+
+        let mut grad_q = self.merge_heads(&grad_q);
+        let mut grad_k = self.merge_heads(&sum_groups(grad_k));
+        if let Some(rotary) = &self.rotary {
+            grad_q = rotary.backward(grad_q, 0);
+            grad_k = rotary.backward(grad_k, 0);
         }
+
+        let from_q = self.query_proj.backward(&cache.input, &grad_q);
+        let from_k = self.key_proj.backward(&cache.input, &grad_k);
+        let from_v = self
+            .value_proj
+            .backward(&cache.input, &self.merge_heads(&sum_groups(grad_v)));
+        &(&from_q + &from_k) + &from_v
     }
 
-    /// Forward pass stub: apply Q/K/V and output projection to token embedding
-    pub fn forward(&self, input: &[f32]) -> Vec<f32> {
-        let q = self.query_proj.forward(input);
-        let k = self.key_proj.forward(input);
+    /// Q/K/V projections of `input`, with queries and keys rotated for
+    /// positions starting at `offset` when `rotary` is set
+    fn project(&self, input: &Tensor, offset: usize) -> (Tensor, Tensor, Tensor) {
+        let mut q = self.query_proj.forward(input);
+        let mut k = self.key_proj.forward(input);
         let v = self.value_proj.forward(input);
-        let mut combined = vec![0.0; q.len()];
-        for i in 0..q.len() {
-            combined[i] = q[i] + k[i] + v[i]; // simplified operation
+        if let Some(rotary) = &self.rotary {
+            q = rotary.forward(q, offset);
+            k = rotary.forward(k, offset);
+        }
+        (q, k, v)
+    }
+
+    /// Attention probabilities `[.., n_heads][query_len][key_len]`; the queries
+    /// are the last `query_len` of the `key_len` positions
+    fn probs(&self, q: &Tensor, k: TensorView, mask: Option<&AttentionMask>) -> Tensor {
+        // [.., n_kv_heads][group][query_len][key_len], then [.., n_heads][query_len][key_len]
+        let scores = self
+            .split_heads(q.view(), self.group_size())
+            .matmul(&self.split_heads(k, 1).t())
+            .scale(self.scale());
+        let mut scores = self.ungroup(scores);
+        if let Some(alibi) = &self.alibi {
+            let n = scores.ndim();
+            let bias = alibi.bias(scores.dim(n - 2), scores.row_len());
+            scores.add_assign(&bias.view());
         }
-        self.out_proj.forward(&combined)
-    }
-
-    /// Forward pass stub: apply Q/K/V and output projection to token embedding
-    /// Zero gradients in all projection layers
-    pub fn zero_grad(&mut self) {
-        self.query_proj.zero_grad();
-        self.key_proj.zero_grad();
-        self.value_proj.zero_grad();
-        self.out_proj.zero_grad();
-    }
-
-    /// Apply parameter gradients using learning rate
-    pub fn apply_grad(&mut self, lr: f32) {
-        self.query_proj.apply_grad(lr);
-        self.key_proj.apply_grad(lr);
-        self.value_proj.apply_grad(lr);
-        self.out_proj.apply_grad(lr);
-    }
-
-    /// Fill all gradients with dummy value for simulation
-    pub fn fill_dummy_grads(&mut self) {
-        for proj in [
-            &mut self.query_proj,
-            &mut self.key_proj,
-            &mut self.value_proj,
-            &mut self.out_proj,
-        ] {
-            proj.weight.grad.iter_mut().for_each(|g| *g = 0.001);
-            proj.bias.grad.iter_mut().for_each(|g| *g = 0.001);
+        if let Some(mask) = mask {
+            scores.add_assign(&mask.per_head());
         }
+        let mut probs = scores;
+        for row in probs.rows_mut() {
+            softmax_in_place(row);
+        }
+        probs
+    }
+
+    /// Mixes the values `v` by `probs` and concatenates the heads of every query
+    fn context(&self, probs: Tensor, v: TensorView) -> Tensor {
+        let context = self.group(probs).view().matmul(&self.split_heads(v, 1));
+        self.merge_heads(&context)
+    }
+
+    /// Query heads sharing each key/value head
+    fn group_size(&self) -> usize {
+        self.n_heads / self.n_kv_heads
+    }
+
+    /// Views `[.., seq_len][n_kv_heads · group · head_dim]` as
+    /// `[.., n_kv_heads][group][seq_len][head_dim]`; `group` is `group_size()`
+    /// for queries and 1 for keys and values
+    fn split_heads<'a>(&self, x: TensorView<'a>, group: usize) -> TensorView<'a> {
+        let mut shape = x.shape().to_vec();
+        shape.pop();
+        shape.extend([self.n_kv_heads, group, self.head_dim]);
+        let n = shape.len();
+        x.reshape(&shape)
+            .transpose(n - 4, n - 3)
+            .transpose(n - 3, n - 2)
+    }
+
+    /// Inverse of `split_heads`, concatenating the heads of every position
+    fn merge_heads(&self, x: &Tensor) -> Tensor {
+        let n = x.ndim();
+        let mut shape = x.shape()[..n - 4].to_vec();
+        shape.extend([x.dim(n - 2), x.dim(n - 4) * x.dim(n - 3) * self.head_dim]);
+        x.view()
+            .transpose(n - 3, n - 2)
+            .transpose(n - 4, n - 3)
+            .to_tensor()
+            .reshape(&shape)
+    }
+
+    /// Splits the head axis of `[.., n_heads][a][b]` into `[.., n_kv_heads][group][a][b]`
+    fn group(&self, x: Tensor) -> Tensor {
+        let n = x.ndim();
+        let mut shape = x.shape()[..n - 3].to_vec();
+        shape.extend([
+            self.n_kv_heads,
+            self.group_size(),
+            x.dim(n - 2),
+            x.dim(n - 1),
+        ]);
+        x.reshape(&shape)
+    }
+
+    /// Inverse of `group`
+    fn ungroup(&self, x: Tensor) -> Tensor {
+        let n = x.ndim();
+        let mut shape = x.shape()[..n - 4].to_vec();
+        shape.extend([self.n_heads, x.dim(n - 2), x.dim(n - 1)]);
+        x.reshape(&shape)
+    }
+
+    fn scale(&self) -> f32 {
+        1.0 / (self.head_dim as f32).sqrt()
     }
 }
+
+impl Module for MultiHeadAttention {
+    fn set_training(&mut self, training: bool) {
+        self.dropout.set_training(training);
+    }
+
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        self.query_proj.visit_params(&join(prefix, "query_proj"), f);
+        self.key_proj.visit_params(&join(prefix, "key_proj"), f);
+        self.value_proj.visit_params(&join(prefix, "value_proj"), f);
+        self.out_proj.visit_params(&join(prefix, "out_proj"), f);
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        self.query_proj
+            .visit_params_mut(&join(prefix, "query_proj"), f);
+        self.key_proj.visit_params_mut(&join(prefix, "key_proj"), f);
+        self.value_proj
+            .visit_params_mut(&join(prefix, "value_proj"), f);
+        self.out_proj.visit_params_mut(&join(prefix, "out_proj"), f);
+    }
+}
+
+/// Numerically stable in-place softmax over a score row
+///
+/// A fully masked row (all `-inf`) yields all-zero probabilities instead of NaN.
+fn softmax_in_place(scores: &mut [f32]) {
+    let max = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    if max == f32::NEG_INFINITY {
+        scores.fill(0.0);
+        return;
+    }
+    for s in scores.iter_mut() {
+        *s = (*s - max).exp();
+    }
+    let sum: f32 = scores.iter().sum();
+    for s in scores.iter_mut() {
+        *s /= sum;
+    }
+}
+
+/// Sums `[.., n_kv_heads][group][a][b]` over the group axis, keeping it with size 1
+fn sum_groups(x: Tensor) -> Tensor {
+    let n = x.ndim();
+    let mut shape = x.shape().to_vec();
+    shape[n - 3] = 1;
+    x.sum_axis(n - 3).reshape(&shape)
+}
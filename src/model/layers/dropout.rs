@@ -0,0 +1,84 @@
+use std::sync::{Mutex, PoisonError};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::model::tensor::Tensor;
+
+/// Inverted dropout: in training mode zeroes each element with probability
+/// `p` and scales the survivors by `1 / (1 - p)`; in eval mode it is the identity
+///
+/// Each layer owns a seeded RNG, so a model built from the same seed drops
+/// the same elements on every run. The RNG sits behind a `Mutex` that is only
+/// locked in training mode, so models stay `Sync` and can serve concurrent
+/// read-only inference.
+#[derive(Debug)]
+pub struct Dropout {
+    pub p: f32,
+    training: bool,
+    rng: Mutex<StdRng>,
+}
+
+impl Dropout {
+    /// Creates a dropout layer in training mode, seeding its RNG from `rng`
+    pub fn new<R: Rng + ?Sized>(p: f32, rng: &mut R) -> Self {
+        assert!(
+            (0.0..1.0).contains(&p),
+            "dropout probability must be in [0, 1), got {p}"
+        );
+        Self {
+            p,
+            training: true,
+            rng: Mutex::new(StdRng::seed_from_u64(rng.random())),
+        }
+    }
+
+    /// A dropout layer that never drops anything
+    pub fn disabled() -> Self {
+        Self {
+            p: 0.0,
+            training: true,
+            rng: Mutex::new(StdRng::seed_from_u64(0)),
+        }
+    }
+
+    pub fn set_training(&mut self, training: bool) {
+        self.training = training;
+    }
+
+    pub fn is_training(&self) -> bool {
+        self.training
+    }
+
+    /// Whether `forward` currently changes its input
+    pub fn is_active(&self) -> bool {
+        self.training && self.p > 0.0
+    }
+
+    /// Applies dropout, also returning the scaled keep mask needed by
+    /// `backward` (`None` when inactive)
+    pub fn forward(&self, input: Tensor) -> (Tensor, Option<Tensor>) {
+        if !self.is_active() {
+            return (input, None);
+        }
+        let scale = 1.0 / (1.0 - self.p);
+        // A panic elsewhere cannot leave the RNG itself half-updated
+        let mut rng = self.rng.lock().unwrap_or_else(PoisonError::into_inner);
+        let mask = input.map(|_| {
+            if rng.random::<f32>() < self.p {
+                0.0
+            } else {
+                scale
+            }
+        });
+        (&input * &mask, Some(mask))
+    }
+
+    /// Gradient of `forward` given the mask it returned
+    pub fn backward(mask: Option<&Tensor>, grad_output: Tensor) -> Tensor {
+        match mask {
+            Some(mask) => &grad_output * mask,
+            None => grad_output,
+        }
+    }
+}
@@ -1,40 +1,93 @@
-use crate::model::{
-    common::Param,
-    consts::{HIDDEN_SIZE, VOCAB_SIZE},
-};
+use rand::Rng;
+
+use crate::model::common::Param;
+use crate::model::init::Init;
+use crate::model::module::{Module, join};
+use crate::model::tensor::{Tensor, TensorView};
+
 /// Token embedding layer using a learnable lookup table
 pub struct TokenEmbedding {
-    pub embeddings: Vec<Param>, // One Param per token ID
+    pub weight: Param, // shape: [vocab_size][dim], row-major
+    pub vocab_size: usize,
+    pub dim: usize,
 }
 
 impl TokenEmbedding {
-    pub fn new() -> Self {
-        let embeddings = (0..VOCAB_SIZE).map(|_| Param::new(HIDDEN_SIZE)).collect();
-        Self { embeddings }
+    pub fn new(vocab_size: usize, dim: usize) -> Self {
+        Self {
+            weight: Param::new(vocab_size * dim),
+            vocab_size,
+            dim,
+        }
+    }
+
+    /// Draws the embedding table from `init`
+    pub fn init_weights<R: Rng + ?Sized>(&mut self, init: Init, rng: &mut R) {
+        init.fill(&mut self.weight.value, self.dim, self.vocab_size, rng);
     }
 
-    pub fn forward(&self, token_ids: &[usize]) -> Vec<Vec<f32>> {
-        token_ids
-            .iter()
-            .map(|&id| self.embeddings[id].value.clone())
-            .collect()
+    /// Embedding row for a single token ID
+    pub fn row(&self, id: usize) -> &[f32] {
+        &self.weight.value[id * self.dim..(id + 1) * self.dim]
     }
 
-    pub fn zero_grad(&mut self) {
-        for param in &mut self.embeddings {
-            param.zero_grad();
+    /// Looks up `token_ids`, returning `[token_ids.len()][dim]`
+    pub fn forward(&self, token_ids: &[usize]) -> Tensor {
+        let mut data = Vec::with_capacity(token_ids.len() * self.dim);
+        for &id in token_ids {
+            data.extend_from_slice(self.row(id));
         }
+        Tensor::new(data, &[token_ids.len(), self.dim])
+    }
+
+    /// Embedding table of shape `[vocab_size][dim]`
+    pub fn table(&self) -> TensorView<'_> {
+        TensorView::new(&self.weight.value, &[self.vocab_size, self.dim])
     }
 
-    pub fn apply_grad(&mut self, lr: f32) {
-        for param in &mut self.embeddings {
-            param.apply_grad(lr);
+    /// Projects hidden states `[.., dim]` onto the vocabulary using the
+    /// transposed embedding table, as used by a weight-tied output head
+    pub fn project(&self, hidden: &Tensor) -> Tensor {
+        assert_eq!(hidden.row_len(), self.dim);
+        let rows = TensorView::new(hidden.data(), &[hidden.num_rows(), self.dim]);
+        let mut shape = hidden.shape().to_vec();
+        *shape.last_mut().expect("hidden states have a last axis") = self.vocab_size;
+        rows.matmul(&self.table().t()).reshape(&shape)
+    }
+
+    /// Backward pass: scatters per-position gradients back into the rows of
+    /// the looked-up tokens
+    pub fn backward(&mut self, token_ids: &[usize], grad_output: &Tensor) {
+        for (&id, grad) in token_ids.iter().zip(grad_output.rows()) {
+            let row = &mut self.weight.grad[id * self.dim..(id + 1) * self.dim];
+            for (g, d) in row.iter_mut().zip(grad) {
+                *g += d;
+            }
         }
     }
 
-    pub fn fill_dummy_grads(&mut self, token_ids: &[usize]) {
-        for &id in token_ids {
-            self.embeddings[id].grad.iter_mut().for_each(|g| *g = 0.001);
+    /// Backward pass of `project`: accumulates table gradients and returns the
+    /// gradient with respect to `hidden`
+    pub fn project_backward(&mut self, hidden: &Tensor, grad_logits: &Tensor) -> Tensor {
+        let rows = TensorView::new(hidden.data(), &[hidden.num_rows(), self.dim]);
+        let grad_rows = TensorView::new(
+            grad_logits.data(),
+            &[grad_logits.num_rows(), self.vocab_size],
+        );
+        let grad_table = grad_rows.clone().t().matmul(&rows);
+        for (acc, g) in self.weight.grad.iter_mut().zip(grad_table.data()) {
+            *acc += g;
         }
+        grad_rows.matmul(&self.table()).reshape(hidden.shape())
+    }
+}
+
+impl Module for TokenEmbedding {
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        f(join(prefix, "weight"), &self.weight);
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        f(join(prefix, "weight"), &mut self.weight);
     }
 }
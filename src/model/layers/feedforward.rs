@@ -0,0 +1,112 @@
+use rand::Rng;
+
+use crate::model::common::Param;
+use crate::model::init::Init;
+use crate::model::layers::{activation::Activation, linear::Linear};
+use crate::model::module::{Module, join};
+use crate::model::tensor::Tensor;
+
+/// Position-wise feed-forward block: up projection, activation, down projection
+///
+/// For gated activations the hidden state is `act(gate_proj(x)) * up_proj(x)`,
+/// as in SwiGLU/GeGLU; otherwise it is `act(up_proj(x))`.
+pub struct FeedForward {
+    pub up_proj: Linear,
+    pub gate_proj: Option<Linear>,
+    pub down_proj: Linear,
+    pub activation: Activation,
+}
+
+/// Intermediate values of one `FeedForward` forward pass needed for backward
+pub struct FeedForwardCache {
+    pub input: Tensor,
+    pub up: Tensor,
+    pub gate: Option<Tensor>,
+    pub hidden: Tensor,
+}
+
+impl FeedForward {
+    pub fn new(d_model: usize, hidden_size: usize, activation: Activation) -> Self {
+        Self {
+            up_proj: Linear::new(d_model, hidden_size),
+            gate_proj: activation
+                .is_gated()
+                .then(|| Linear::new(d_model, hidden_size)),
+            down_proj: Linear::new(hidden_size, d_model),
+            activation,
+        }
+    }
+
+    /// Draws every projection's weights from `init`
+    pub fn init_weights<R: Rng + ?Sized>(&mut self, init: Init, rng: &mut R) {
+        self.up_proj.init_weights(init, rng);
+        if let Some(gate_proj) = &mut self.gate_proj {
+            gate_proj.init_weights(init, rng);
+        }
+        self.down_proj.init_weights(init, rng);
+    }
+
+    /// Forward pass applied independently to every position of `[.., d_model]`
+    pub fn forward(&self, input: &Tensor) -> Tensor {
+        self.forward_with_cache(input).0
+    }
+
+    /// Forward pass that also returns the values needed by `backward`
+    pub fn forward_with_cache(&self, input: &Tensor) -> (Tensor, FeedForwardCache) {
+        let up = self.up_proj.forward(input);
+        let gate = self.gate_proj.as_ref().map(|g| g.forward(input));
+        let hidden = match &gate {
+            Some(gate) => gate.zip_map(&up, |g, u| self.activation.apply(g) * u),
+            None => up.map(|u| self.activation.apply(u)),
+        };
+        let output = self.down_proj.forward(&hidden);
+        let cache = FeedForwardCache {
+            input: input.clone(),
+            up,
+            gate,
+            hidden,
+        };
+        (output, cache)
+    }
+
+    /// Backward pass: accumulates projection gradients and returns the
+    /// gradient with respect to the block input
+    pub fn backward(&mut self, cache: &FeedForwardCache, grad_output: &Tensor) -> Tensor {
+        let grad_hidden = self.down_proj.backward(&cache.hidden, grad_output);
+        match (&mut self.gate_proj, &cache.gate) {
+            (Some(gate_proj), Some(gate)) => {
+                let activation = self.activation;
+                let grad_up = grad_hidden.zip_map(gate, |dh, g| dh * activation.apply(g));
+                let grad_gate =
+                    (&grad_hidden * &cache.up).zip_map(gate, |d, g| d * activation.derivative(g));
+                let from_up = self.up_proj.backward(&cache.input, &grad_up);
+                let from_gate = gate_proj.backward(&cache.input, &grad_gate);
+                &from_up + &from_gate
+            }
+            _ => {
+                let grad_up =
+                    grad_hidden.zip_map(&cache.up, |dh, u| dh * self.activation.derivative(u));
+                self.up_proj.backward(&cache.input, &grad_up)
+            }
+        }
+    }
+}
+
+impl Module for FeedForward {
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        self.up_proj.visit_params(&join(prefix, "up_proj"), f);
+        if let Some(gate_proj) = &self.gate_proj {
+            gate_proj.visit_params(&join(prefix, "gate_proj"), f);
+        }
+        self.down_proj.visit_params(&join(prefix, "down_proj"), f);
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        self.up_proj.visit_params_mut(&join(prefix, "up_proj"), f);
+        if let Some(gate_proj) = &mut self.gate_proj {
+            gate_proj.visit_params_mut(&join(prefix, "gate_proj"), f);
+        }
+        self.down_proj
+            .visit_params_mut(&join(prefix, "down_proj"), f);
+    }
+}
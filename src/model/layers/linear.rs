@@ -1,4 +1,9 @@
+use rand::Rng;
+
 use crate::model::common::Param;
+use crate::model::init::Init;
+use crate::model::module::{Module, join};
+use crate::model::tensor::{Tensor, TensorView};
 
 /// A simple linear (fully connected) layer with learnable parameters
 pub struct Linear {
@@ -9,7 +14,8 @@ pub struct Linear {
 }
 
 impl Linear {
-    /// Creates a new linear layer with randomized weights and zero biases
+    /// Creates a new linear layer with zero parameters; call `init_weights`
+    /// to draw the weights from an initialization scheme
     pub fn new(in_features: usize, out_features: usize) -> Self {
         let weight = Param::new(in_features * out_features);
         let bias = Param::new(out_features);
@@ -21,28 +27,89 @@ impl Linear {
         }
     }
 
-    /// Forward pass: input is a flattened vector, returns linear output
-    pub fn forward(&self, input: &[f32]) -> Vec<f32> {
-        assert_eq!(input.len(), self.in_features);
-        let mut output = vec![0.0; self.out_features];
-        for i in 0..self.out_features {
-            for j in 0..self.in_features {
-                output[i] += self.weight.value[i * self.in_features + j] * input[j];
+    /// Forward pass over `[.., in_features]`, returning `[.., out_features]`
+    ///
+    /// All leading axes are flattened into rows and multiplied with the
+    /// weight matrix in a single product.
+    pub fn forward(&self, input: &Tensor) -> Tensor {
+        let rows = self.rows(input);
+        let mut output = rows.matmul(&self.weight().t());
+        output.add_assign(&TensorView::new(&self.bias.value, &[self.out_features]));
+        output.reshape(&self.output_shape(input.shape()))
+    }
+
+    /// Backward pass: accumulates weight and bias gradients for the given
+    /// forward `input` and returns the gradient with respect to that input
+    pub fn backward(&mut self, input: &Tensor, grad_output: &Tensor) -> Tensor {
+        assert_eq!(
+            grad_output.shape(),
+            self.output_shape(input.shape()).as_slice()
+        );
+        let rows = self.rows(input);
+        let grad_rows = TensorView::new(
+            grad_output.data(),
+            &[grad_output.num_rows(), self.out_features],
+        );
+
+        // dW = gᵀ·x, db = Σ g, dx = g·W
+        let grad_weight = grad_rows.clone().t().matmul(&rows);
+        for (acc, g) in self.weight.grad.iter_mut().zip(grad_weight.data()) {
+            *acc += g;
+        }
+        for row in grad_output.rows() {
+            for (acc, g) in self.bias.grad.iter_mut().zip(row) {
+                *acc += g;
             }
-            output[i] += self.bias.value[i];
         }
-        output
+        grad_rows.matmul(&self.weight()).reshape(input.shape())
+    }
+
+    /// Draws the weights from `init` and zeroes the biases
+    pub fn init_weights<R: Rng + ?Sized>(&mut self, init: Init, rng: &mut R) {
+        init.fill(
+            &mut self.weight.value,
+            self.in_features,
+            self.out_features,
+            rng,
+        );
+        self.bias.value.fill(0.0);
     }
 
-    /// Zero out all gradients in weight and bias
-    pub fn zero_grad(&mut self) {
-        self.weight.zero_grad();
-        self.bias.zero_grad();
+    /// Weight matrix of shape `[out_features][in_features]`
+    pub fn weight(&self) -> TensorView<'_> {
+        TensorView::new(&self.weight.value, &[self.out_features, self.in_features])
+    }
+
+    /// Flattens `input` into `[rows][in_features]`
+    fn rows<'a>(&self, input: &'a Tensor) -> TensorView<'a> {
+        assert_eq!(
+            input.row_len(),
+            self.in_features,
+            "expected input of shape [.., {}], got {:?}",
+            self.in_features,
+            input.shape()
+        );
+        TensorView::new(input.data(), &[input.num_rows(), self.in_features])
+    }
+
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        let mut shape = input_shape.to_vec();
+        match shape.last_mut() {
+            Some(last) => *last = self.out_features,
+            None => shape.push(self.out_features),
+        }
+        shape
+    }
+}
+
+impl Module for Linear {
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        f(join(prefix, "weight"), &self.weight);
+        f(join(prefix, "bias"), &self.bias);
     }
 
-    /// Apply gradients to update weight and bias using learning rate
-    pub fn apply_grad(&mut self, lr: f32) {
-        self.weight.apply_grad(lr);
-        self.bias.apply_grad(lr);
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        f(join(prefix, "weight"), &mut self.weight);
+        f(join(prefix, "bias"), &mut self.bias);
     }
 }
@@ -1,5 +1,10 @@
+pub mod activation;
+pub mod alibi;
 pub mod attention;
+pub mod dropout;
 pub mod embedding;
+pub mod feedforward;
 pub mod linear;
 pub mod norm;
 pub mod positional;
+pub mod rotary;
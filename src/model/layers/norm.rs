@@ -1,29 +1,238 @@
+use crate::model::common::Param;
+use crate::model::module::{Module, join};
+use crate::model::tensor::Tensor;
+
+/// Normalization applied around the attention and feed-forward sublayers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormKind {
+    /// Mean/variance normalization with scale and shift (GPT, BERT)
+    #[default]
+    LayerNorm,
+    /// Root-mean-square normalization with scale only (T5, LLaMA)
+    RmsNorm,
+}
+
 /// Layer Normalization layer
 pub struct LayerNorm {
-    pub gamma: Vec<f32>,
-    pub beta: Vec<f32>,
+    pub gamma: Param,
+    pub beta: Param,
     pub epsilon: f32,
 }
 
 impl LayerNorm {
     pub fn new(dim: usize) -> Self {
         Self {
-            gamma: vec![1.0; dim],
-            beta: vec![0.0; dim],
+            gamma: Param::from_values(vec![1.0; dim]),
+            beta: Param::from_values(vec![0.0; dim]),
             epsilon: 1e-5,
         }
     }
 
-    /// Forward pass of LayerNorm
-    pub fn forward(&self, input: &[f32]) -> Vec<f32> {
-        let mean = input.iter().sum::<f32>() / input.len() as f32;
-        let variance = input.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / input.len() as f32;
-        input
+    /// Resets gamma to ones and beta to zeros
+    pub fn reset_parameters(&mut self) {
+        self.gamma.value.fill(1.0);
+        self.beta.value.fill(0.0);
+    }
+
+    /// Forward pass of LayerNorm, normalizing over the last axis
+    pub fn forward(&self, input: &Tensor) -> Tensor {
+        let mut output = input.clone();
+        for row in output.rows_mut() {
+            let (mean, inv_std) = self.stats(row);
+            for (x, (g, b)) in row
+                .iter_mut()
+                .zip(self.gamma.value.iter().zip(&self.beta.value))
+            {
+                *x = g * (*x - mean) * inv_std + b;
+            }
+        }
+        output
+    }
+
+    /// Backward pass: accumulates gamma/beta gradients for the given forward
+    /// `input` and returns the gradient with respect to that input
+    pub fn backward(&mut self, input: &Tensor, grad_output: &Tensor) -> Tensor {
+        assert_eq!(input.shape(), grad_output.shape());
+        let mut grad_input = Vec::with_capacity(input.len());
+        for (x, dy) in input.rows().zip(grad_output.rows()) {
+            grad_input.extend(self.backward_row(x, dy));
+        }
+        Tensor::new(grad_input, input.shape())
+    }
+
+    fn backward_row(&mut self, input: &[f32], grad_output: &[f32]) -> Vec<f32> {
+        let n = input.len() as f32;
+        let (mean, inv_std) = self.stats(input);
+        let x_hat: Vec<f32> = input.iter().map(|x| (x - mean) * inv_std).collect();
+
+        let mut grad_x_hat = Vec::with_capacity(input.len());
+        for (i, (&dy, &xh)) in grad_output.iter().zip(&x_hat).enumerate() {
+            self.gamma.grad[i] += dy * xh;
+            self.beta.grad[i] += dy;
+            grad_x_hat.push(dy * self.gamma.value[i]);
+        }
+
+        let mean_grad = grad_x_hat.iter().sum::<f32>() / n;
+        let mean_grad_x_hat = grad_x_hat
+            .iter()
+            .zip(&x_hat)
+            .map(|(g, xh)| g * xh)
+            .sum::<f32>()
+            / n;
+        grad_x_hat
             .iter()
-            .enumerate()
-            .map(|(i, x)| {
-                self.gamma[i] * ((x - mean) / (variance + self.epsilon).sqrt()) + self.beta[i]
-            })
+            .zip(&x_hat)
+            .map(|(g, xh)| inv_std * (g - mean_grad - xh * mean_grad_x_hat))
             .collect()
     }
+
+    /// Returns the mean and reciprocal standard deviation of `input`
+    fn stats(&self, input: &[f32]) -> (f32, f32) {
+        let mean = input.iter().sum::<f32>() / input.len() as f32;
+        let variance = input.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / input.len() as f32;
+        (mean, 1.0 / (variance + self.epsilon).sqrt())
+    }
+}
+
+impl Module for LayerNorm {
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        f(join(prefix, "gamma"), &self.gamma);
+        f(join(prefix, "beta"), &self.beta);
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        f(join(prefix, "gamma"), &mut self.gamma);
+        f(join(prefix, "beta"), &mut self.beta);
+    }
+}
+
+/// Root Mean Square Normalization: `x / √(mean(x²) + ε) · gamma`
+///
+/// Unlike `LayerNorm` it neither centers the input nor adds a bias.
+pub struct RMSNorm {
+    pub gamma: Param,
+    pub epsilon: f32,
+}
+
+impl RMSNorm {
+    pub fn new(dim: usize) -> Self {
+        Self {
+            gamma: Param::from_values(vec![1.0; dim]),
+            epsilon: 1e-6,
+        }
+    }
+
+    /// Resets gamma to ones
+    pub fn reset_parameters(&mut self) {
+        self.gamma.value.fill(1.0);
+    }
+
+    /// Forward pass of RMSNorm, normalizing over the last axis
+    pub fn forward(&self, input: &Tensor) -> Tensor {
+        let mut output = input.clone();
+        for row in output.rows_mut() {
+            let inv_rms = self.inv_rms(row);
+            for (x, g) in row.iter_mut().zip(&self.gamma.value) {
+                *x *= g * inv_rms;
+            }
+        }
+        output
+    }
+
+    /// Backward pass: accumulates gamma gradients for the given forward
+    /// `input` and returns the gradient with respect to that input
+    pub fn backward(&mut self, input: &Tensor, grad_output: &Tensor) -> Tensor {
+        assert_eq!(input.shape(), grad_output.shape());
+        let mut grad_input = Vec::with_capacity(input.len());
+        for (x, dy) in input.rows().zip(grad_output.rows()) {
+            let inv_rms = self.inv_rms(x);
+            let mut grad_x_hat = Vec::with_capacity(x.len());
+            for (i, (&dy, &x)) in dy.iter().zip(x).enumerate() {
+                self.gamma.grad[i] += dy * x * inv_rms;
+                grad_x_hat.push(dy * self.gamma.value[i]);
+            }
+
+            let mean_grad_x_hat = grad_x_hat
+                .iter()
+                .zip(x)
+                .map(|(g, x)| g * x * inv_rms)
+                .sum::<f32>()
+                / x.len() as f32;
+            grad_input.extend(
+                grad_x_hat
+                    .iter()
+                    .zip(x)
+                    .map(|(g, x)| inv_rms * (g - x * inv_rms * mean_grad_x_hat)),
+            );
+        }
+        Tensor::new(grad_input, input.shape())
+    }
+
+    /// Returns the reciprocal root mean square of `input`
+    fn inv_rms(&self, input: &[f32]) -> f32 {
+        let mean_square = input.iter().map(|x| x * x).sum::<f32>() / input.len() as f32;
+        1.0 / (mean_square + self.epsilon).sqrt()
+    }
+}
+
+impl Module for RMSNorm {
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        f(join(prefix, "gamma"), &self.gamma);
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        f(join(prefix, "gamma"), &mut self.gamma);
+    }
+}
+
+/// A `LayerNorm` or `RMSNorm`, as chosen by `NormKind`
+pub enum Norm {
+    Layer(LayerNorm),
+    Rms(RMSNorm),
+}
+
+impl Norm {
+    pub fn new(kind: NormKind, dim: usize) -> Self {
+        match kind {
+            NormKind::LayerNorm => Norm::Layer(LayerNorm::new(dim)),
+            NormKind::RmsNorm => Norm::Rms(RMSNorm::new(dim)),
+        }
+    }
+
+    pub fn reset_parameters(&mut self) {
+        match self {
+            Norm::Layer(norm) => norm.reset_parameters(),
+            Norm::Rms(norm) => norm.reset_parameters(),
+        }
+    }
+
+    pub fn forward(&self, input: &Tensor) -> Tensor {
+        match self {
+            Norm::Layer(norm) => norm.forward(input),
+            Norm::Rms(norm) => norm.forward(input),
+        }
+    }
+
+    pub fn backward(&mut self, input: &Tensor, grad_output: &Tensor) -> Tensor {
+        match self {
+            Norm::Layer(norm) => norm.backward(input, grad_output),
+            Norm::Rms(norm) => norm.backward(input, grad_output),
+        }
+    }
+}
+
+impl Module for Norm {
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        match self {
+            Norm::Layer(norm) => norm.visit_params(prefix, f),
+            Norm::Rms(norm) => norm.visit_params(prefix, f),
+        }
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        match self {
+            Norm::Layer(norm) => norm.visit_params_mut(prefix, f),
+            Norm::Rms(norm) => norm.visit_params_mut(prefix, f),
+        }
+    }
 }
@@ -1,19 +1,25 @@
-use crate::model::consts::{HIDDEN_SIZE, MAX_SEQ_LEN};
+use std::fmt;
+
+use rand::Rng;
+
+use crate::model::common::Param;
+use crate::model::init::Init;
+use crate::model::module::{Module, join};
+use crate::model::tensor::{Tensor, TensorView};
 
 /// PositionalEncoding generates sinusoidal position encodings for sequences
 pub struct PositionalEncoding {
-    pub encoding: Vec<Vec<f32>>, // shape: [MAX_SEQ_LEN][HIDDEN_SIZE]
+    pub encoding: Tensor, // shape: [max_seq_len][dim]
 }
 
 impl PositionalEncoding {
-    pub fn new() -> Self {
-        let mut encoding = vec![vec![0.0; HIDDEN_SIZE]; MAX_SEQ_LEN];
-
-        for pos in 0..MAX_SEQ_LEN {
-            for i in 0..HIDDEN_SIZE {
-                let angle =
-                    pos as f32 / f32::powf(10000.0, (2 * (i / 2)) as f32 / HIDDEN_SIZE as f32);
-                encoding[pos][i] = if i % 2 == 0 { angle.sin() } else { angle.cos() };
+    pub fn new(max_seq_len: usize, dim: usize) -> Self {
+        let mut encoding = Tensor::zeros(&[max_seq_len, dim]);
+
+        for (pos, row) in encoding.rows_mut().enumerate() {
+            for (i, value) in row.iter_mut().enumerate() {
+                let angle = pos as f32 / f32::powf(10000.0, (2 * (i / 2)) as f32 / dim as f32);
+                *value = if i % 2 == 0 { angle.sin() } else { angle.cos() };
             }
         }
 
@@ -21,7 +27,172 @@ impl PositionalEncoding {
     }
 
     /// Fetch positional encoding for a given sequence length
-    pub fn get_encoding(&self, seq_len: usize) -> &[Vec<f32>] {
-        &self.encoding[0..seq_len]
+    pub fn get_encoding(&self, seq_len: usize) -> Result<TensorView<'_>, SequenceTooLong> {
+        self.get_range(0, seq_len)
+    }
+
+    /// Encodings of positions `start..start + len`, `[len][dim]`
+    pub fn get_range(&self, start: usize, len: usize) -> Result<TensorView<'_>, SequenceTooLong> {
+        check_seq_len(start + len, self.encoding.dim(0))?;
+        Ok(self.encoding.view().narrow(0, start, len))
+    }
+}
+
+/// Learnable absolute position embeddings: one trainable row per position
+pub struct LearnedPositionalEmbedding {
+    pub weight: Param, // shape: [max_seq_len][dim], row-major
+    pub max_seq_len: usize,
+    pub dim: usize,
+}
+
+impl LearnedPositionalEmbedding {
+    pub fn new(max_seq_len: usize, dim: usize) -> Self {
+        Self {
+            weight: Param::new(max_seq_len * dim),
+            max_seq_len,
+            dim,
+        }
+    }
+
+    /// Draws the embedding table from `init`
+    pub fn init_weights<R: Rng + ?Sized>(&mut self, init: Init, rng: &mut R) {
+        init.fill(&mut self.weight.value, self.dim, self.max_seq_len, rng);
+    }
+
+    /// Embeddings of the first `seq_len` positions, `[seq_len][dim]`
+    pub fn get_encoding(&self, seq_len: usize) -> Result<TensorView<'_>, SequenceTooLong> {
+        self.get_range(0, seq_len)
+    }
+
+    /// Embeddings of positions `start..start + len`, `[len][dim]`
+    pub fn get_range(&self, start: usize, len: usize) -> Result<TensorView<'_>, SequenceTooLong> {
+        check_seq_len(start + len, self.max_seq_len)?;
+        Ok(TensorView::new(
+            &self.weight.value[start * self.dim..(start + len) * self.dim],
+            &[len, self.dim],
+        ))
+    }
+
+    /// Backward pass: accumulates the gradient of every position, summed over
+    /// any leading batch axes of `grad_output` (`[.., seq_len][dim]`)
+    pub fn backward(&mut self, grad_output: &Tensor) {
+        let seq_len = grad_output.dim(grad_output.ndim() - 2);
+        for (i, grad) in grad_output.rows().enumerate() {
+            let pos = i % seq_len;
+            let row = &mut self.weight.grad[pos * self.dim..(pos + 1) * self.dim];
+            for (g, d) in row.iter_mut().zip(grad) {
+                *g += d;
+            }
+        }
+    }
+}
+
+impl Module for LearnedPositionalEmbedding {
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        f(join(prefix, "weight"), &self.weight);
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        f(join(prefix, "weight"), &mut self.weight);
+    }
+}
+
+/// Absolute position information added to the token embeddings
+pub enum PositionEmbedding {
+    /// Fixed sinusoids, not trained
+    Sinusoidal(PositionalEncoding),
+    /// A trainable table, selected by `ModelConfig::learned_positional_encoding`
+    Learned(LearnedPositionalEmbedding),
+}
+
+impl PositionEmbedding {
+    pub fn new(max_seq_len: usize, dim: usize, learned: bool) -> Self {
+        if learned {
+            Self::Learned(LearnedPositionalEmbedding::new(max_seq_len, dim))
+        } else {
+            Self::Sinusoidal(PositionalEncoding::new(max_seq_len, dim))
+        }
+    }
+
+    /// Longest sequence that has a position embedding
+    pub fn max_seq_len(&self) -> usize {
+        match self {
+            Self::Sinusoidal(pos) => pos.encoding.dim(0),
+            Self::Learned(pos) => pos.max_seq_len,
+        }
+    }
+
+    /// Draws a learned table from `init`; sinusoids are left untouched
+    pub fn init_weights<R: Rng + ?Sized>(&mut self, init: Init, rng: &mut R) {
+        if let Self::Learned(pos) = self {
+            pos.init_weights(init, rng);
+        }
+    }
+
+    /// Embeddings of the first `seq_len` positions, `[seq_len][dim]`
+    pub fn get_encoding(&self, seq_len: usize) -> Result<TensorView<'_>, SequenceTooLong> {
+        match self {
+            Self::Sinusoidal(pos) => pos.get_encoding(seq_len),
+            Self::Learned(pos) => pos.get_encoding(seq_len),
+        }
+    }
+
+    /// Embeddings of positions `start..start + len`, `[len][dim]`
+    pub fn get_range(&self, start: usize, len: usize) -> Result<TensorView<'_>, SequenceTooLong> {
+        match self {
+            Self::Sinusoidal(pos) => pos.get_range(start, len),
+            Self::Learned(pos) => pos.get_range(start, len),
+        }
+    }
+
+    /// Accumulates the gradient of a learned table; a no-op for sinusoids
+    pub fn backward(&mut self, grad_output: &Tensor) {
+        if let Self::Learned(pos) = self {
+            pos.backward(grad_output);
+        }
+    }
+}
+
+impl Module for PositionEmbedding {
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        if let Self::Learned(pos) = self {
+            pos.visit_params(prefix, f);
+        }
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        if let Self::Learned(pos) = self {
+            pos.visit_params_mut(prefix, f);
+        }
+    }
+}
+
+/// A sequence is longer than the positions the model was built for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceTooLong {
+    pub seq_len: usize,
+    pub max_seq_len: usize,
+}
+
+impl fmt::Display for SequenceTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sequence length {} exceeds `max_seq_len` ({})",
+            self.seq_len, self.max_seq_len
+        )
+    }
+}
+
+impl std::error::Error for SequenceTooLong {}
+
+/// Fails when `seq_len` positions do not fit in `max_seq_len`
+pub fn check_seq_len(seq_len: usize, max_seq_len: usize) -> Result<(), SequenceTooLong> {
+    if seq_len > max_seq_len {
+        return Err(SequenceTooLong {
+            seq_len,
+            max_seq_len,
+        });
     }
+    Ok(())
 }
@@ -0,0 +1,96 @@
+use crate::model::tensor::Tensor;
+
+/// Rotary position embeddings (RoPE) applied to attention queries and keys
+///
+/// Within each head, features `i` and `i + rotary_dim / 2` of the first
+/// `rotary_dim` features are rotated together by `pos · base^(-2i / rotary_dim)`
+/// (the "rotate half" layout used by LLaMA and GPT-NeoX); the remaining
+/// features pass through unchanged.
+pub struct RotaryEmbedding {
+    pub base: f32,
+    pub head_dim: usize,
+    pub rotary_dim: usize,
+    cos: Tensor, // shape: [max_seq_len][rotary_dim / 2]
+    sin: Tensor,
+}
+
+impl RotaryEmbedding {
+    /// Precomputes the rotation angles of `max_seq_len` positions, rotating
+    /// `fraction` of each head's `head_dim` features
+    pub fn new(head_dim: usize, max_seq_len: usize, base: f32, fraction: f32) -> Self {
+        let rotary_dim = rotary_dim(head_dim, fraction);
+        assert!(
+            rotary_dim >= 2 && rotary_dim <= head_dim,
+            "rotary fraction {fraction} of head_dim {head_dim} leaves no feature pairs to rotate"
+        );
+        let half = rotary_dim / 2;
+        let mut cos = Tensor::zeros(&[max_seq_len, half]);
+        let mut sin = Tensor::zeros(&[max_seq_len, half]);
+        for (pos, (cos_row, sin_row)) in cos.rows_mut().zip(sin.rows_mut()).enumerate() {
+            for (i, (c, s)) in cos_row.iter_mut().zip(sin_row).enumerate() {
+                let angle = pos as f32 * base.powf(-2.0 * i as f32 / rotary_dim as f32);
+                *c = angle.cos();
+                *s = angle.sin();
+            }
+        }
+        Self {
+            base,
+            head_dim,
+            rotary_dim,
+            cos,
+            sin,
+        }
+    }
+
+    /// Number of positions with precomputed angles
+    pub fn max_seq_len(&self) -> usize {
+        self.cos.dim(0)
+    }
+
+    /// Rotates every head of `x` (`[.., seq_len][n_heads · head_dim]`), whose
+    /// first row sits at absolute position `offset`
+    pub fn forward(&self, x: Tensor, offset: usize) -> Tensor {
+        self.rotate(x, offset, 1.0)
+    }
+
+    /// Gradient of `forward`: the rotation is orthogonal, so the gradient is
+    /// rotated back by the same angles
+    pub fn backward(&self, grad_output: Tensor, offset: usize) -> Tensor {
+        self.rotate(grad_output, offset, -1.0)
+    }
+
+    fn rotate(&self, mut x: Tensor, offset: usize, direction: f32) -> Tensor {
+        assert!(
+            x.row_len().is_multiple_of(self.head_dim),
+            "rotary input rows must hold whole heads of {} features",
+            self.head_dim
+        );
+        let seq_len = x.dim(x.ndim() - 2);
+        assert!(
+            offset + seq_len <= self.max_seq_len(),
+            "position {} exceeds the {} positions of the rotary embedding",
+            offset + seq_len,
+            self.max_seq_len()
+        );
+        let half = self.rotary_dim / 2;
+        for (i, row) in x.rows_mut().enumerate() {
+            let pos = offset + i % seq_len;
+            let (cos, sin) = (self.cos.row(pos), self.sin.row(pos));
+            for head in row.chunks_mut(self.head_dim) {
+                for j in 0..half {
+                    let (a, b) = (head[j], head[j + half]);
+                    let s = direction * sin[j];
+                    head[j] = a * cos[j] - b * s;
+                    head[j + half] = b * cos[j] + a * s;
+                }
+            }
+        }
+        x
+    }
+}
+
+/// Number of rotated features per head: `fraction · head_dim`, rounded down
+/// to an even count
+pub fn rotary_dim(head_dim: usize, fraction: f32) -> usize {
+    ((head_dim as f32 * fraction) as usize) / 2 * 2
+}
@@ -0,0 +1,170 @@
+use crate::model::tensor::{Tensor, TensorView};
+
+/// Additive attention mask of shape `[query_len][key_len]`, or
+/// `[batch][query_len][key_len]` for a batch of sequences
+///
+/// Each entry is added to the raw attention score before the softmax:
+/// `0.0` keeps the score, `f32::NEG_INFINITY` blocks the key entirely and
+/// any other finite value acts as a bias. An unbatched mask applies to every
+/// sequence of a batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttentionMask {
+    pub bias: Tensor,
+}
+
+impl AttentionMask {
+    /// A mask that lets every query attend to every key
+    pub fn none(query_len: usize, key_len: usize) -> Self {
+        Self {
+            bias: Tensor::zeros(&[query_len, key_len]),
+        }
+    }
+
+    /// Lower-triangular mask: position `i` may only attend to positions `<= i`
+    pub fn causal(seq_len: usize) -> Self {
+        let bias = (0..seq_len)
+            .flat_map(|i| (0..seq_len).map(move |j| if j <= i { 0.0 } else { f32::NEG_INFINITY }))
+            .collect();
+        Self {
+            bias: Tensor::new(bias, &[seq_len, seq_len]),
+        }
+    }
+
+    /// Causal mask for `query_len` queries that are the last of `key_len`
+    /// positions, as when decoding with a KV cache
+    pub fn causal_with_offset(query_len: usize, key_len: usize) -> Self {
+        let offset = key_len - query_len;
+        let bias = (0..query_len)
+            .flat_map(|i| {
+                (0..key_len).map(move |j| {
+                    if j <= offset + i {
+                        0.0
+                    } else {
+                        f32::NEG_INFINITY
+                    }
+                })
+            })
+            .collect();
+        Self {
+            bias: Tensor::new(bias, &[query_len, key_len]),
+        }
+    }
+
+    /// Blocks every key whose token equals `pad_id`, for all queries
+    pub fn key_padding(token_ids: &[usize], pad_id: usize) -> Self {
+        let keep: Vec<bool> = token_ids.iter().map(|&id| id != pad_id).collect();
+        Self::from_padding(&[keep]).unbatched()
+    }
+
+    /// Batched key-padding mask from a `[batch][seq_len]` padding mask where
+    /// `true` marks a real token and `false` a padded one
+    pub fn from_padding(attention_mask: &[Vec<bool>]) -> Self {
+        let seq_len = attention_mask.first().map_or(0, Vec::len);
+        let mut bias = Vec::with_capacity(attention_mask.len() * seq_len * seq_len);
+        for keep in attention_mask {
+            assert_eq!(
+                keep.len(),
+                seq_len,
+                "all sequences in a batch must have the same length"
+            );
+            let row = keep
+                .iter()
+                .map(|&k| if k { 0.0 } else { f32::NEG_INFINITY });
+            for _ in 0..seq_len {
+                bias.extend(row.clone());
+            }
+        }
+        Self {
+            bias: Tensor::new(bias, &[attention_mask.len(), seq_len, seq_len]),
+        }
+    }
+
+    /// Builds a mask from booleans where `true` means "may attend"
+    pub fn from_bool(allowed: &[Vec<bool>]) -> Self {
+        let bias: Vec<Vec<f32>> = allowed
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&ok| if ok { 0.0 } else { f32::NEG_INFINITY })
+                    .collect()
+            })
+            .collect();
+        Self::from_additive(bias)
+    }
+
+    /// Wraps an arbitrary additive bias matrix
+    pub fn from_additive(bias: Vec<Vec<f32>>) -> Self {
+        Self {
+            bias: Tensor::from_rows(&bias),
+        }
+    }
+
+    /// Stacks per-sequence masks of equal shape into one batched mask
+    pub fn stack(masks: &[AttentionMask]) -> Self {
+        let shape = masks
+            .first()
+            .map_or(vec![0, 0], |m| m.bias.shape().to_vec());
+        let mut bias = Vec::new();
+        for mask in masks {
+            assert_eq!(
+                mask.bias.shape(),
+                shape.as_slice(),
+                "stacked masks must share one unbatched shape"
+            );
+            bias.extend_from_slice(mask.bias.data());
+        }
+        Self {
+            bias: Tensor::new(bias, &[masks.len(), shape[0], shape[1]]),
+        }
+    }
+
+    /// Combines two masks by summing their biases; a key blocked by either stays blocked
+    ///
+    /// An unbatched mask is broadcast over the batch of the other one.
+    pub fn combine(&self, other: &AttentionMask) -> Self {
+        assert_eq!(self.query_len(), other.query_len());
+        assert_eq!(self.key_len(), other.key_len());
+        Self {
+            bias: &self.bias + &other.bias,
+        }
+    }
+
+    /// Number of sequences for a batched mask, `None` if it applies to any batch
+    pub fn batch_size(&self) -> Option<usize> {
+        (self.bias.ndim() == 3).then(|| self.bias.dim(0))
+    }
+
+    pub fn query_len(&self) -> usize {
+        self.bias.dim(self.bias.ndim() - 2)
+    }
+
+    pub fn key_len(&self) -> usize {
+        self.bias.row_len()
+    }
+
+    /// Returns the additive bias row for query position `i` of an unbatched mask
+    pub fn row(&self, i: usize) -> &[f32] {
+        assert!(self.batch_size().is_none(), "row() needs an unbatched mask");
+        self.bias.row(i)
+    }
+
+    /// The bias shaped to broadcast over attention scores
+    /// `[.., n_heads][query_len][key_len]`
+    pub fn per_head(&self) -> TensorView<'_> {
+        match self.batch_size() {
+            Some(batch) => self
+                .bias
+                .view()
+                .reshape(&[batch, 1, self.query_len(), self.key_len()]),
+            None => self.bias.view(),
+        }
+    }
+
+    /// Drops the batch axis of a mask holding a single sequence
+    fn unbatched(self) -> Self {
+        let shape = [self.query_len(), self.key_len()];
+        Self {
+            bias: self.bias.reshape(&shape),
+        }
+    }
+}
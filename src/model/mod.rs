@@ -1,5 +1,11 @@
+pub mod block;
 pub mod common;
 pub mod config;
 pub mod consts;
+pub mod init;
+pub mod kv_cache;
 pub mod layers;
+pub mod mask;
+pub mod module;
+pub mod tensor;
 pub mod transformer;
@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::model::common::Param;
+
+/// Named parameter values, keyed by hierarchical names such as
+/// `layers.0.attn.query_proj.weight`
+pub type StateDict = BTreeMap<String, Vec<f32>>;
+
+/// A component owning learnable parameters
+///
+/// Implementors only describe how to reach their parameters; iteration,
+/// gradient bookkeeping and (de)serialization are provided on top of that.
+pub trait Module {
+    /// Calls `f` with the full name and a reference of every parameter,
+    /// prefixing local names with `prefix`
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param));
+
+    /// Mutable counterpart of `visit_params`
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param));
+
+    /// Switches train-only behaviour such as dropout on or off; modules
+    /// without any keep the default no-op
+    fn set_training(&mut self, _training: bool) {}
+
+    /// Puts the module in training mode
+    fn train(&mut self) {
+        self.set_training(true);
+    }
+
+    /// Puts the module in evaluation mode, making forward passes deterministic
+    fn eval(&mut self) {
+        self.set_training(false);
+    }
+
+    /// Every parameter with its hierarchical name, in a stable order
+    fn parameters(&self) -> Vec<(String, &Param)> {
+        let mut params = Vec::new();
+        self.visit_params("", &mut |name, param| params.push((name, param)));
+        params
+    }
+
+    /// Every parameter with its hierarchical name, in the same order as `parameters`
+    fn parameters_mut(&mut self) -> Vec<(String, &mut Param)> {
+        let mut params = Vec::new();
+        self.visit_params_mut("", &mut |name, param| params.push((name, param)));
+        params
+    }
+
+    /// Total number of scalar parameters
+    fn num_parameters(&self) -> usize {
+        let mut count = 0;
+        self.visit_params("", &mut |_, param| count += param.value.len());
+        count
+    }
+
+    /// Clears all parameter gradients
+    fn zero_grad(&mut self) {
+        self.visit_params_mut("", &mut |_, param| param.zero_grad());
+    }
+
+    /// Applies accumulated gradients with plain gradient descent
+    fn apply_grad(&mut self, lr: f32) {
+        self.visit_params_mut("", &mut |_, param| param.apply_grad(lr));
+    }
+
+    /// Copies every parameter value into a name-keyed map
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        self.visit_params("", &mut |name, param| {
+            state.insert(name, param.value.clone());
+        });
+        state
+    }
+
+    /// Overwrites parameter values from `state`
+    ///
+    /// Loading is strict: every parameter must be present with a matching
+    /// length and `state` may not contain unknown names. Nothing is modified
+    /// unless the whole dict is compatible.
+    fn load_state_dict(&mut self, state: &StateDict) -> Result<(), StateDictError> {
+        let mut expected = Vec::new();
+        self.visit_params("", &mut |name, param| {
+            expected.push((name, param.value.len()))
+        });
+
+        for (name, len) in &expected {
+            match state.get(name) {
+                None => return Err(StateDictError::Missing(name.clone())),
+                Some(values) if values.len() != *len => {
+                    return Err(StateDictError::ShapeMismatch {
+                        name: name.clone(),
+                        expected: *len,
+                        found: values.len(),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        if let Some(name) = state
+            .keys()
+            .find(|key| !expected.iter().any(|(name, _)| name == *key))
+        {
+            return Err(StateDictError::Unexpected(name.clone()));
+        }
+
+        self.visit_params_mut("", &mut |name, param| {
+            param.value.copy_from_slice(&state[&name]);
+        });
+        Ok(())
+    }
+}
+
+/// Joins a parent prefix and a local parameter/child name with a dot
+pub fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{prefix}.{name}")
+    }
+}
+
+/// Reasons a `StateDict` cannot be loaded into a module
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateDictError {
+    /// A parameter of the module has no entry in the dict
+    Missing(String),
+    /// The dict holds a name the module does not have
+    Unexpected(String),
+    /// Entry length differs from the parameter size
+    ShapeMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for StateDictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateDictError::Missing(name) => write!(f, "missing parameter `{name}`"),
+            StateDictError::Unexpected(name) => write!(f, "unexpected parameter `{name}`"),
+            StateDictError::ShapeMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "parameter `{name}` has {found} values, expected {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StateDictError {}
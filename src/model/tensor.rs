@@ -0,0 +1,516 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+/// Dense `f32` tensor stored contiguously in row-major order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    data: Vec<f32>,
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+}
+
+/// Borrowed, possibly non-contiguous view into tensor data
+///
+/// Transposing and narrowing a view only rewrites its shape, strides and
+/// offset; no data is copied until `to_tensor` is called.
+#[derive(Debug, Clone)]
+pub struct TensorView<'a> {
+    data: &'a [f32],
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+    offset: usize,
+}
+
+impl Tensor {
+    /// Wraps `data` with the given shape; panics if the sizes disagree
+    pub fn new(data: Vec<f32>, shape: &[usize]) -> Self {
+        assert_eq!(
+            data.len(),
+            shape.iter().product::<usize>(),
+            "data of length {} does not fit shape {shape:?}",
+            data.len()
+        );
+        Self {
+            data,
+            shape: shape.to_vec(),
+            strides: contiguous_strides(shape),
+        }
+    }
+
+    pub fn zeros(shape: &[usize]) -> Self {
+        Self::full(shape, 0.0)
+    }
+
+    pub fn full(shape: &[usize], value: f32) -> Self {
+        Self::new(vec![value; shape.iter().product()], shape)
+    }
+
+    /// Builds a `[rows.len()][width]` matrix from equally long rows
+    pub fn from_rows(rows: &[Vec<f32>]) -> Self {
+        let width = rows.first().map_or(0, Vec::len);
+        assert!(
+            rows.iter().all(|r| r.len() == width),
+            "rows must all have length {width}"
+        );
+        Self::new(rows.concat(), &[rows.len(), width])
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn strides(&self) -> &[usize] {
+        &self.strides
+    }
+
+    pub fn ndim(&self) -> usize {
+        self.shape.len()
+    }
+
+    /// Size of dimension `axis`
+    pub fn dim(&self, axis: usize) -> usize {
+        self.shape[axis]
+    }
+
+    /// Total number of elements
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> Vec<f32> {
+        self.data
+    }
+
+    /// Element at a multi-dimensional index
+    pub fn get(&self, index: &[usize]) -> f32 {
+        self.data[offset_of(&self.shape, &self.strides, 0, index)]
+    }
+
+    /// Mutable element at a multi-dimensional index
+    pub fn get_mut(&mut self, index: &[usize]) -> &mut f32 {
+        &mut self.data[offset_of(&self.shape, &self.strides, 0, index)]
+    }
+
+    /// Size of the last dimension, i.e. the length of each row
+    pub fn row_len(&self) -> usize {
+        self.shape.last().copied().unwrap_or(1)
+    }
+
+    /// Number of rows when all leading dimensions are flattened
+    pub fn num_rows(&self) -> usize {
+        self.len() / self.row_len().max(1)
+    }
+
+    /// Row `i` of the tensor flattened to `[num_rows][row_len]`
+    pub fn row(&self, i: usize) -> &[f32] {
+        let n = self.row_len();
+        &self.data[i * n..(i + 1) * n]
+    }
+
+    /// Iterates over last-axis rows
+    pub fn rows(&self) -> impl Iterator<Item = &[f32]> {
+        self.data.chunks_exact(self.row_len().max(1))
+    }
+
+    /// Iterates mutably over last-axis rows
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [f32]> {
+        let n = self.row_len().max(1);
+        self.data.chunks_exact_mut(n)
+    }
+
+    /// Copies the rows into nested vectors
+    pub fn to_rows(&self) -> Vec<Vec<f32>> {
+        self.rows().map(<[f32]>::to_vec).collect()
+    }
+
+    pub fn view(&self) -> TensorView<'_> {
+        TensorView {
+            data: &self.data,
+            shape: self.shape.clone(),
+            strides: self.strides.clone(),
+            offset: 0,
+        }
+    }
+
+    /// Reinterprets the data with a new shape of the same size
+    pub fn reshape(self, shape: &[usize]) -> Self {
+        Self::new(self.data, shape)
+    }
+
+    /// Swaps two axes, materializing the result contiguously
+    pub fn transpose(&self, a: usize, b: usize) -> Self {
+        self.view().transpose(a, b).to_tensor()
+    }
+
+    /// Contiguous copy of `len` entries of `axis` starting at `start`
+    pub fn narrow(&self, axis: usize, start: usize, len: usize) -> Self {
+        self.view().narrow(axis, start, len).to_tensor()
+    }
+
+    /// Applies `f` to every element
+    pub fn map(&self, mut f: impl FnMut(f32) -> f32) -> Self {
+        Self::new(self.data.iter().map(|&x| f(x)).collect(), &self.shape)
+    }
+
+    /// Element-wise combination with broadcasting
+    pub fn zip_map(&self, other: &Tensor, f: impl Fn(f32, f32) -> f32) -> Self {
+        self.view().zip_map(&other.view(), f)
+    }
+
+    /// Multiplies every element by `factor`
+    pub fn scale(&self, factor: f32) -> Self {
+        self.map(|x| x * factor)
+    }
+
+    /// In-place `self += other`, broadcasting `other` to this tensor's shape
+    pub fn add_assign(&mut self, other: &TensorView) {
+        let shape = broadcast_shape(&self.shape, other.shape())
+            .filter(|s| *s == self.shape)
+            .unwrap_or_else(|| {
+                panic!("cannot broadcast {:?} into {:?}", other.shape(), self.shape)
+            });
+        let offsets = StridedOffsets::new(&shape, &broadcast_strides(other, &shape), other.offset);
+        for (x, o) in self.data.iter_mut().zip(offsets) {
+            *x += other.data[o];
+        }
+    }
+
+    /// Sums over `axis`, removing it from the shape
+    pub fn sum_axis(&self, axis: usize) -> Self {
+        let outer: usize = self.shape[..axis].iter().product();
+        let size = self.shape[axis];
+        let inner: usize = self.shape[axis + 1..].iter().product();
+        let mut out = vec![0.0; outer * inner];
+        for o in 0..outer {
+            for s in 0..size {
+                let src = &self.data[(o * size + s) * inner..(o * size + s + 1) * inner];
+                for (acc, x) in out[o * inner..(o + 1) * inner].iter_mut().zip(src) {
+                    *acc += x;
+                }
+            }
+        }
+        let mut shape = self.shape.clone();
+        shape.remove(axis);
+        Self::new(out, &shape)
+    }
+
+    /// Sum of all elements
+    pub fn sum(&self) -> f32 {
+        self.data.iter().sum()
+    }
+
+    /// Matrix product over the last two axes, broadcasting leading batch axes
+    pub fn matmul(&self, other: &Tensor) -> Self {
+        self.view().matmul(&other.view())
+    }
+}
+
+impl<'a> TensorView<'a> {
+    /// Contiguous view of `data` with the given shape
+    pub fn new(data: &'a [f32], shape: &[usize]) -> Self {
+        assert_eq!(
+            data.len(),
+            shape.iter().product::<usize>(),
+            "data of length {} does not fit shape {shape:?}",
+            data.len()
+        );
+        Self {
+            data,
+            shape: shape.to_vec(),
+            strides: contiguous_strides(shape),
+            offset: 0,
+        }
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn strides(&self) -> &[usize] {
+        &self.strides
+    }
+
+    pub fn ndim(&self) -> usize {
+        self.shape.len()
+    }
+
+    /// Total number of elements
+    pub fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_contiguous(&self) -> bool {
+        self.strides == contiguous_strides(&self.shape)
+    }
+
+    /// Element at a multi-dimensional index
+    pub fn get(&self, index: &[usize]) -> f32 {
+        self.data[offset_of(&self.shape, &self.strides, self.offset, index)]
+    }
+
+    /// Swaps two axes without copying
+    pub fn transpose(mut self, a: usize, b: usize) -> Self {
+        self.shape.swap(a, b);
+        self.strides.swap(a, b);
+        self
+    }
+
+    /// Swaps the last two axes without copying
+    pub fn t(self) -> Self {
+        let n = self.ndim();
+        assert!(n >= 2, "t() needs at least two dimensions, got {n}");
+        self.transpose(n - 2, n - 1)
+    }
+
+    /// Restricts `axis` to `len` entries starting at `start`
+    pub fn narrow(mut self, axis: usize, start: usize, len: usize) -> Self {
+        assert!(
+            start + len <= self.shape[axis],
+            "range {start}..{} out of bounds for axis {axis} of size {}",
+            start + len,
+            self.shape[axis]
+        );
+        self.offset += start * self.strides[axis];
+        self.shape[axis] = len;
+        self
+    }
+
+    /// Fixes `axis` at `index`, removing it from the shape
+    pub fn select(mut self, axis: usize, index: usize) -> Self {
+        assert!(index < self.shape[axis], "index {index} out of bounds");
+        self.offset += index * self.strides[axis];
+        self.shape.remove(axis);
+        self.strides.remove(axis);
+        self
+    }
+
+    /// Reinterprets a contiguous view with a new shape of the same size
+    pub fn reshape(self, shape: &[usize]) -> Self {
+        assert!(
+            self.is_contiguous(),
+            "only contiguous views can be reshaped"
+        );
+        let len = self.len();
+        TensorView::new(&self.data[self.offset..self.offset + len], shape)
+    }
+
+    /// Copies the viewed elements into a contiguous tensor
+    pub fn to_tensor(&self) -> Tensor {
+        let offsets = StridedOffsets::new(&self.shape, &self.strides, self.offset);
+        Tensor::new(offsets.map(|o| self.data[o]).collect(), &self.shape)
+    }
+
+    /// Element-wise combination with numpy-style broadcasting
+    pub fn zip_map(&self, other: &TensorView, f: impl Fn(f32, f32) -> f32) -> Tensor {
+        let shape = broadcast_shape(&self.shape, &other.shape).unwrap_or_else(|| {
+            panic!(
+                "shapes {:?} and {:?} cannot be broadcast",
+                self.shape, other.shape
+            )
+        });
+        let lhs = StridedOffsets::new(&shape, &broadcast_strides(self, &shape), self.offset);
+        let rhs = StridedOffsets::new(&shape, &broadcast_strides(other, &shape), other.offset);
+        let data = lhs
+            .zip(rhs)
+            .map(|(a, b)| f(self.data[a], other.data[b]))
+            .collect();
+        Tensor::new(data, &shape)
+    }
+
+    pub fn add(&self, other: &TensorView) -> Tensor {
+        self.zip_map(other, |a, b| a + b)
+    }
+
+    pub fn sub(&self, other: &TensorView) -> Tensor {
+        self.zip_map(other, |a, b| a - b)
+    }
+
+    pub fn mul(&self, other: &TensorView) -> Tensor {
+        self.zip_map(other, |a, b| a * b)
+    }
+
+    pub fn div(&self, other: &TensorView) -> Tensor {
+        self.zip_map(other, |a, b| a / b)
+    }
+
+    /// Matrix product `[.., m, k] · [.., k, n] -> [.., m, n]`
+    ///
+    /// Leading batch axes are broadcast against each other, so a 2-D weight
+    /// can be applied to a whole `[batch, seq, k]` input. Strided operands
+    /// (e.g. from `t()`) are read in place.
+    pub fn matmul(&self, other: &TensorView) -> Tensor {
+        assert!(
+            self.ndim() >= 2 && other.ndim() >= 2,
+            "matmul needs at least two dimensions, got {:?} and {:?}",
+            self.shape,
+            other.shape
+        );
+        let (a_batch, [m, k]) = split_matrix(&self.shape);
+        let (b_batch, [k2, n]) = split_matrix(&other.shape);
+        assert_eq!(
+            k, k2,
+            "matmul inner dimensions differ: {:?} · {:?}",
+            self.shape, other.shape
+        );
+        let batch = broadcast_shape(a_batch, b_batch).unwrap_or_else(|| {
+            panic!("batch shapes {a_batch:?} and {b_batch:?} cannot be broadcast")
+        });
+
+        let a_strides = broadcast_strides(self, &[batch.as_slice(), &[m, k]].concat());
+        let b_strides = broadcast_strides(other, &[batch.as_slice(), &[k, n]].concat());
+        let nb = batch.len();
+        let (sa_m, sa_k) = (a_strides[nb], a_strides[nb + 1]);
+        let (sb_k, sb_n) = (b_strides[nb], b_strides[nb + 1]);
+
+        let a_bases = StridedOffsets::new(&batch, &a_strides[..nb], self.offset);
+        let b_bases = StridedOffsets::new(&batch, &b_strides[..nb], other.offset);
+        let mut out = vec![0.0; batch.iter().product::<usize>() * m * n];
+        for ((a_base, b_base), block) in a_bases.zip(b_bases).zip(out.chunks_exact_mut(m * n)) {
+            for i in 0..m {
+                let out_row = &mut block[i * n..(i + 1) * n];
+                for p in 0..k {
+                    let a = self.data[a_base + i * sa_m + p * sa_k];
+                    let b_row = b_base + p * sb_k;
+                    for (j, o) in out_row.iter_mut().enumerate() {
+                        *o += a * other.data[b_row + j * sb_n];
+                    }
+                }
+            }
+        }
+        Tensor::new(out, &[batch.as_slice(), &[m, n]].concat())
+    }
+}
+
+macro_rules! impl_binary_op {
+    ($trait:ident, $method:ident) => {
+        impl $trait<&Tensor> for &Tensor {
+            type Output = Tensor;
+
+            fn $method(self, rhs: &Tensor) -> Tensor {
+                self.view().$method(&rhs.view())
+            }
+        }
+    };
+}
+
+impl_binary_op!(Add, add);
+impl_binary_op!(Sub, sub);
+impl_binary_op!(Mul, mul);
+impl_binary_op!(Div, div);
+
+/// Row-major strides for a contiguous tensor of `shape`
+fn contiguous_strides(shape: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; shape.len()];
+    for i in (0..shape.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+    strides
+}
+
+fn offset_of(shape: &[usize], strides: &[usize], base: usize, index: &[usize]) -> usize {
+    assert_eq!(
+        index.len(),
+        shape.len(),
+        "index {index:?} does not match shape {shape:?}"
+    );
+    index
+        .iter()
+        .zip(shape)
+        .zip(strides)
+        .fold(base, |acc, ((&i, &dim), &stride)| {
+            assert!(i < dim, "index {index:?} out of bounds for shape {shape:?}");
+            acc + i * stride
+        })
+}
+
+/// Splits `[.., m, n]` into its batch axes and matrix dimensions
+fn split_matrix(shape: &[usize]) -> (&[usize], [usize; 2]) {
+    let n = shape.len();
+    (&shape[..n - 2], [shape[n - 2], shape[n - 1]])
+}
+
+/// Result shape of broadcasting `a` against `b`, aligning trailing axes
+fn broadcast_shape(a: &[usize], b: &[usize]) -> Option<Vec<usize>> {
+    let ndim = a.len().max(b.len());
+    let mut shape = vec![0; ndim];
+    for (i, dim) in shape.iter_mut().enumerate() {
+        let x = (i + a.len()).checked_sub(ndim).map_or(1, |j| a[j]);
+        let y = (i + b.len()).checked_sub(ndim).map_or(1, |j| b[j]);
+        *dim = match (x, y) {
+            (x, y) if x == y => x,
+            (1, y) => y,
+            (x, 1) => x,
+            _ => return None,
+        };
+    }
+    Some(shape)
+}
+
+/// Strides reading `view` as if it had `shape`; broadcast axes get stride 0
+fn broadcast_strides(view: &TensorView, shape: &[usize]) -> Vec<usize> {
+    let pad = shape.len() - view.ndim();
+    (0..shape.len())
+        .map(|i| match i.checked_sub(pad) {
+            Some(j) if view.shape[j] == shape[i] => view.strides[j],
+            _ => 0,
+        })
+        .collect()
+}
+
+/// Iterates over the data offsets of a strided layout in row-major order
+struct StridedOffsets {
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+    index: Vec<usize>,
+    offset: usize,
+    remaining: usize,
+}
+
+impl StridedOffsets {
+    fn new(shape: &[usize], strides: &[usize], offset: usize) -> Self {
+        Self {
+            shape: shape.to_vec(),
+            strides: strides.to_vec(),
+            index: vec![0; shape.len()],
+            offset,
+            remaining: shape.iter().product(),
+        }
+    }
+}
+
+impl Iterator for StridedOffsets {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let current = self.offset;
+        for axis in (0..self.shape.len()).rev() {
+            self.index[axis] += 1;
+            self.offset += self.strides[axis];
+            if self.index[axis] < self.shape[axis] {
+                break;
+            }
+            self.offset -= self.strides[axis] * self.shape[axis];
+            self.index[axis] = 0;
+        }
+        Some(current)
+    }
+}
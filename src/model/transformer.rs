@@ -1,121 +1,582 @@
+use rand::Rng;
+
+use crate::model::block::{BlockCache, TransformerBlock};
 use crate::model::layers::{
-    attention::MultiHeadAttention, embedding::TokenEmbedding, linear::Linear, norm::LayerNorm,
-    positional::PositionalEncoding,
+    dropout::Dropout,
+    embedding::TokenEmbedding,
+    linear::Linear,
+    norm::Norm,
+    positional::{PositionEmbedding, SequenceTooLong, check_seq_len},
 };
 
-use crate::model::consts::{HIDDEN_SIZE, NUM_LAYERS};
+use crate::model::common::Param;
+use crate::model::config::{ConfigError, ModelConfig, PositionalEncodingKind};
+use crate::model::consts::{HIDDEN_SIZE, MAX_SEQ_LEN, NUM_HEADS, NUM_LAYERS, VOCAB_SIZE};
+use crate::model::init::seeded_rng;
+use crate::model::kv_cache::KvCache;
+use crate::model::mask::AttentionMask;
+use crate::model::module::{Module, join};
+use crate::model::tensor::Tensor;
+use crate::tokenizer::PAD_TOKEN_ID;
+use crate::training::optimizer::ParamGroup;
 
 /// A simple transformer model with token and positional embeddings
 pub struct SimpleTransformer {
+    pub config: ModelConfig,
     pub token_embedding: TokenEmbedding,
-    pub pos_encoding: PositionalEncoding,
+    /// Sinusoidal or learned absolute positions, per `learned_positional_encoding`;
+    /// `None` when positions are encoded inside attention (RoPE, ALiBi)
+    pub pos_encoding: Option<PositionEmbedding>,
     pub hidden_size: usize,
-    pub attention_layers: Vec<MultiHeadAttention>,
-    pub attn_norms: Vec<LayerNorm>,
-    pub ff_layers: Vec<Linear>,
-    pub ff_norms: Vec<LayerNorm>,
+    pub layers: Vec<TransformerBlock>,
+    /// Norm applied to the last hidden state before the output head (Pre-LN only)
+    pub final_norm: Option<Norm>,
+    /// Output projection to vocabulary logits; `None` when tied to `token_embedding`
+    pub lm_head: Option<Linear>,
+    /// Whether attention is restricted to earlier positions (decoder-style)
+    pub causal: bool,
+    /// Dropout on the summed token and positional embeddings
+    pub embedding_dropout: Dropout,
+}
+
+/// Intermediate values of one model forward pass needed for backward
+pub struct ForwardCache {
+    /// Token IDs of every position, flattened in row-major order
+    pub token_ids: Vec<usize>,
+    pub blocks: Vec<BlockCache>,
+    /// Output of the last block, i.e. the input of `final_norm`
+    pub final_norm_input: Tensor,
+    /// Final hidden states fed to the LM head
+    pub hidden: Tensor,
+    /// Keep mask of the embedding dropout, if it was active
+    pub embedding_dropout_mask: Option<Tensor>,
+}
+
+impl Default for SimpleTransformer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SimpleTransformer {
-    /// Initializes a new transformer model
+    /// Initializes a new transformer model sized by `model::consts`
     pub fn new() -> Self {
-        let token_embedding = TokenEmbedding::new();
-        let pos_encoding = PositionalEncoding::new();
+        let config = ModelConfig {
+            d_model: HIDDEN_SIZE,
+            n_heads: NUM_HEADS,
+            n_layers: NUM_LAYERS,
+            max_seq_len: MAX_SEQ_LEN,
+            vocab_size: VOCAB_SIZE,
+            ..ModelConfig::default()
+        };
+        Self::from_config(&config).expect("default model constants form a valid config")
+    }
 
-        let mut attention_layers = Vec::new();
-        let mut attn_norms = Vec::new();
-        let mut ff_layers = Vec::new();
-        let mut ff_norms = Vec::new();
+    /// Builds a transformer sized by `config`, validating it first
+    pub fn from_config(config: &ModelConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
 
-        for _ in 0..NUM_LAYERS {
-            attention_layers.push(MultiHeadAttention::new(HIDDEN_SIZE));
-            attn_norms.push(LayerNorm::new(HIDDEN_SIZE));
-            ff_layers.push(Linear::new(HIDDEN_SIZE, HIDDEN_SIZE));
-            ff_norms.push(LayerNorm::new(HIDDEN_SIZE));
-        }
+        let mut rng = seeded_rng(config.seed);
+        let d_model = config.d_model;
+        let token_embedding = TokenEmbedding::new(config.vocab_size, d_model);
+        let pos_encoding = match config.positional_encoding {
+            PositionalEncodingKind::Absolute => Some(PositionEmbedding::new(
+                config.max_seq_len,
+                d_model,
+                config.learned_positional_encoding,
+            )),
+            PositionalEncodingKind::Rotary { .. } | PositionalEncodingKind::Alibi => None,
+        };
+
+        let layers = (0..config.n_layers)
+            .map(|_| TransformerBlock::new(config, &mut rng))
+            .collect();
 
-        Self {
+        // Post-LN blocks already end in a norm, Pre-LN stacks need one before the head
+        let final_norm = config
+            .pre_layer_norm
+            .then(|| Norm::new(config.norm, d_model));
+
+        let lm_head = if config.weight_sharing {
+            None
+        } else {
+            Some(Linear::new(d_model, config.vocab_size))
+        };
+
+        let mut model = Self {
+            config: config.clone(),
             token_embedding,
             pos_encoding,
-            hidden_size: HIDDEN_SIZE,
-            attention_layers,
-            attn_norms,
-            ff_layers,
-            ff_norms,
+            hidden_size: d_model,
+            layers,
+            final_norm,
+            lm_head,
+            causal: config.causal,
+            embedding_dropout: Dropout::new(config.dropout, &mut rng),
+        };
+        model.init_weights(&mut rng);
+        Ok(model)
+    }
+
+    /// (Re)initializes every parameter according to `config.init`
+    ///
+    /// Parameters are visited in a fixed order, so the same RNG state always
+    /// yields a bit-identical model.
+    pub fn init_weights<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let init = self.config.init;
+        let residual_scale = if self.config.scaled_residual_init {
+            1.0 / (2.0 * self.layers.len() as f32).sqrt()
+        } else {
+            1.0
+        };
+
+        self.token_embedding.init_weights(init, rng);
+        if let Some(pos) = &mut self.pos_encoding {
+            pos.init_weights(init, rng);
+        }
+        for layer in &mut self.layers {
+            layer.init_weights(init, residual_scale, rng);
+        }
+        if let Some(norm) = &mut self.final_norm {
+            norm.reset_parameters();
+        }
+        if let Some(head) = &mut self.lm_head {
+            head.init_weights(init, rng);
         }
     }
 
-    /// Forward pass from token IDs to final vector output
-    pub fn forward(&self, token_ids: &[usize]) -> Vec<f32> {
-        let seq_len = token_ids.len();
-        let token_embeds = self.token_embedding.forward(token_ids);
-        let pos_enc = self.pos_encoding.get_encoding(seq_len);
+    /// Longest sequence the model accepts, `None` when unbounded (ALiBi);
+    /// longer inputs make the forward passes panic, the `try_*` variants
+    /// return an error instead
+    pub fn max_seq_len(&self) -> Option<usize> {
+        match self.config.positional_encoding {
+            PositionalEncodingKind::Alibi => None,
+            _ => Some(self.config.max_seq_len),
+        }
+    }
+
+    /// Checks that a sequence of `seq_len` positions fits within `max_seq_len`
+    pub fn check_input_len(&self, seq_len: usize) -> Result<(), SequenceTooLong> {
+        match self.max_seq_len() {
+            Some(max_seq_len) => check_seq_len(seq_len, max_seq_len),
+            None => Ok(()),
+        }
+    }
+
+    /// Builds the default attention mask for `token_ids`
+    ///
+    /// Keys holding the `<pad>` token are always blocked; when `causal` is set
+    /// each position is further restricted to itself and earlier positions.
+    pub fn default_mask(&self, token_ids: &[usize]) -> AttentionMask {
+        let padding = AttentionMask::key_padding(token_ids, PAD_TOKEN_ID);
+        if self.causal {
+            padding.combine(&AttentionMask::causal(token_ids.len()))
+        } else {
+            padding
+        }
+    }
 
-        // Add positional encoding to token embeddings
-        let mut x: Vec<Vec<f32>> = token_embeds
+    /// Builds the default mask for a padded `[batch][seq_len]` batch: the
+    /// key-padding mask of every sequence, plus causal masking when `causal` is set
+    pub fn default_batch_mask(&self, token_ids: &[Vec<usize>]) -> AttentionMask {
+        let keep: Vec<Vec<bool>> = token_ids
             .iter()
-            .zip(pos_enc.iter())
-            .map(|(embed, pos)| embed.iter().zip(pos).map(|(a, b)| a + b).collect())
+            .map(|seq| seq.iter().map(|&id| id != PAD_TOKEN_ID).collect())
             .collect();
+        let padding = AttentionMask::from_padding(&keep);
+        if self.causal {
+            padding.combine(&AttentionMask::causal(padding.query_len()))
+        } else {
+            padding
+        }
+    }
 
-        // Apply each transformer layer
-        for i in 0..self.attention_layers.len() {
-            x = x
-                .into_iter()
-                .map(|vec| {
-                    let attn_out = self.attention_layers[i].forward(&vec);
-                    let normed = self.attn_norms[i].forward(&attn_out);
-                    let ff_out = self.ff_layers[i].forward(&normed);
-                    self.ff_norms[i].forward(&ff_out)
-                })
-                .collect();
-        }
-
-        // Mean pooling across sequence
+    /// Forward pass from token IDs to per-position vocabulary logits
+    /// `[seq_len][vocab_size]` using the default mask
+    pub fn forward(&self, token_ids: &[usize]) -> Tensor {
+        let mask = self.default_mask(token_ids);
+        self.forward_with_mask(token_ids, Some(&mask))
+    }
+
+    /// `forward` that returns an error instead of panicking on over-length input
+    pub fn try_forward(&self, token_ids: &[usize]) -> Result<Tensor, SequenceTooLong> {
+        self.check_input_len(token_ids.len())?;
+        Ok(self.forward(token_ids))
+    }
+
+    /// Forward pass to vocabulary logits with a caller-supplied attention mask
+    ///
+    /// The mask is used as-is, so callers wanting causal or padding behaviour
+    /// must combine it themselves.
+    pub fn forward_with_mask(&self, token_ids: &[usize], mask: Option<&AttentionMask>) -> Tensor {
+        self.forward_with_cache(token_ids, mask).0
+    }
+
+    /// Forward pass to vocabulary logits that also returns the values needed by `backward`
+    pub fn forward_with_cache(
+        &self,
+        token_ids: &[usize],
+        mask: Option<&AttentionMask>,
+    ) -> (Tensor, ForwardCache) {
+        let cache = self.encode(token_ids, &[token_ids.len()], mask);
+        let logits = self.logits(&cache.hidden);
+        (logits, cache)
+    }
+
+    /// Batched forward pass from padded `[batch][seq_len]` token IDs to logits
+    /// `[batch][seq_len][vocab_size]`
+    ///
+    /// `padding_mask` marks real tokens with `true`, as produced alongside
+    /// padded batches; when omitted it is derived from `<pad>` tokens. Causal
+    /// masking is added when `causal` is set.
+    pub fn forward_batch(
+        &self,
+        token_ids: &[Vec<usize>],
+        padding_mask: Option<&[Vec<bool>]>,
+    ) -> Tensor {
+        let mask = match padding_mask {
+            Some(keep) => {
+                let padding = AttentionMask::from_padding(keep);
+                if self.causal {
+                    padding.combine(&AttentionMask::causal(padding.query_len()))
+                } else {
+                    padding
+                }
+            }
+            None => self.default_batch_mask(token_ids),
+        };
+        self.forward_batch_with_cache(token_ids, Some(&mask)).0
+    }
+
+    /// `forward_batch` that returns an error instead of panicking on over-length input
+    pub fn try_forward_batch(
+        &self,
+        token_ids: &[Vec<usize>],
+        padding_mask: Option<&[Vec<bool>]>,
+    ) -> Result<Tensor, SequenceTooLong> {
+        self.check_input_len(token_ids.first().map_or(0, Vec::len))?;
+        Ok(self.forward_batch(token_ids, padding_mask))
+    }
+
+    /// Batched forward pass with a caller-supplied (batched or shared) mask that
+    /// also returns the values needed by `backward`
+    pub fn forward_batch_with_cache(
+        &self,
+        token_ids: &[Vec<usize>],
+        mask: Option<&AttentionMask>,
+    ) -> (Tensor, ForwardCache) {
+        let seq_len = token_ids.first().map_or(0, Vec::len);
+        assert!(
+            token_ids.iter().all(|seq| seq.len() == seq_len),
+            "batched sequences must be padded to the same length"
+        );
+        let cache = self.encode(&token_ids.concat(), &[token_ids.len(), seq_len], mask);
+        let logits = self.logits(&cache.hidden);
+        (logits, cache)
+    }
+
+    /// Empty KV cache for decoding one sequence with `forward_incremental`
+    pub fn new_kv_cache(&self) -> KvCache {
+        let kv_size = self
+            .layers
+            .first()
+            .map_or(0, |layer| layer.attn.n_kv_heads * layer.attn.head_dim);
+        KvCache::new(self.layers.len(), kv_size)
+    }
+
+    /// Runs the next `token_ids` of a sequence whose earlier positions are in
+    /// `kv_cache`, returning their logits `[token_ids.len()][vocab_size]`
+    ///
+    /// The new keys and values are appended to the cache, so feeding a prompt
+    /// and then one token at a time matches `forward` on the whole sequence
+    /// while only computing the new positions. Causal masking is applied when
+    /// `causal` is set; `<pad>` tokens are not masked.
+    pub fn forward_incremental(&self, token_ids: &[usize], kv_cache: &mut KvCache) -> Tensor {
+        self.try_forward_incremental(token_ids, kv_cache)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// `forward_incremental` that returns an error, leaving `kv_cache`
+    /// untouched, when the cached and new positions exceed `max_seq_len`
+    pub fn try_forward_incremental(
+        &self,
+        token_ids: &[usize],
+        kv_cache: &mut KvCache,
+    ) -> Result<Tensor, SequenceTooLong> {
+        assert_eq!(
+            kv_cache.layers.len(),
+            self.layers.len(),
+            "KV cache has a different number of layers than the model"
+        );
+        let start = kv_cache.len();
+        let len = token_ids.len();
+        self.check_input_len(start + len)?;
+
+        let mut x = self.token_embedding.forward(token_ids);
+        if let Some(pos) = &self.pos_encoding {
+            let positions = pos.get_range(start, len).expect("length checked above");
+            x.add_assign(&positions);
+        }
+        x = self.embedding_dropout.forward(x).0;
+
+        let mask = self
+            .causal
+            .then(|| AttentionMask::causal_with_offset(len, start + len));
+        for (layer, layer_cache) in self.layers.iter().zip(&mut kv_cache.layers) {
+            x = layer.forward_incremental(&x, layer_cache, mask.as_ref());
+        }
+        if let Some(norm) = &self.final_norm {
+            x = norm.forward(&x);
+        }
+        Ok(self.logits(&x))
+    }
+
+    /// Logits of the token following `sequence`, used by the decoding loops
+    ///
+    /// Causal models only run the positions of `sequence` missing from
+    /// `kv_cache`; non-causal ones cannot reuse cached keys, so they rerun the
+    /// whole sequence. `<pad>` gets a logit of `-inf`: `forward` masks it as
+    /// padding while the KV cache does not, so the two paths would disagree after it.
+    pub fn next_token_logits(&self, sequence: &[usize], kv_cache: &mut KvCache) -> Vec<f32> {
+        let logits = if self.causal {
+            self.forward_incremental(&sequence[kv_cache.len()..], kv_cache)
+        } else {
+            self.forward(sequence)
+        };
+        let mut logits = logits.row(logits.num_rows() - 1).to_vec();
+        logits[PAD_TOKEN_ID] = f32::NEG_INFINITY;
+        logits
+    }
+
+    /// Mean-pooled sequence embedding of size `hidden_size` using the default mask
+    pub fn pooled_output(&self, token_ids: &[usize]) -> Vec<f32> {
+        let mask = self.default_mask(token_ids);
+        self.pooled_output_with_mask(token_ids, Some(&mask))
+    }
+
+    /// Mean-pooled sequence embedding with a caller-supplied attention mask
+    ///
+    /// `<pad>` positions never contribute to the pooled output.
+    pub fn pooled_output_with_mask(
+        &self,
+        token_ids: &[usize],
+        mask: Option<&AttentionMask>,
+    ) -> Vec<f32> {
+        let x = self.hidden_states(token_ids, mask);
+
+        // Mean pooling across non-padding positions
         let mut final_vec = vec![0.0; self.hidden_size];
-        for token_vec in &x {
-            for i in 0..self.hidden_size {
-                final_vec[i] += token_vec[i];
+        let mut count = 0;
+        for (token_vec, _) in x
+            .rows()
+            .zip(token_ids)
+            .filter(|(_, id)| **id != PAD_TOKEN_ID)
+        {
+            for (acc, v) in final_vec.iter_mut().zip(token_vec) {
+                *acc += v;
             }
+            count += 1;
         }
-        for val in &mut final_vec {
-            *val /= seq_len as f32;
+        if count > 0 {
+            for val in &mut final_vec {
+                *val /= count as f32;
+            }
         }
 
         final_vec
     }
 
-    /// Clears all parameter gradients
-    pub fn zero_grad(&mut self) {
-        self.token_embedding.zero_grad();
-        for attn in &mut self.attention_layers {
-            attn.zero_grad();
+    /// Runs the embeddings and every transformer layer, returning the final
+    /// hidden state of each position `[seq_len][hidden_size]`
+    pub fn hidden_states(&self, token_ids: &[usize], mask: Option<&AttentionMask>) -> Tensor {
+        self.encode(token_ids, &[token_ids.len()], mask).hidden
+    }
+
+    /// Runs the model on flattened `token_ids` laid out as `shape`, either
+    /// `[seq_len]` or `[batch][seq_len]`
+    fn encode(
+        &self,
+        token_ids: &[usize],
+        shape: &[usize],
+        mask: Option<&AttentionMask>,
+    ) -> ForwardCache {
+        let seq_len = *shape.last().expect("token shape has a sequence axis");
+        let mut embed_shape = shape.to_vec();
+        embed_shape.push(self.hidden_size);
+
+        self.check_input_len(seq_len)
+            .unwrap_or_else(|err| panic!("{err}"));
+
+        // Add absolute positional encoding to token embeddings
+        let mut x = self
+            .token_embedding
+            .forward(token_ids)
+            .reshape(&embed_shape);
+        if let Some(pos) = &self.pos_encoding {
+            let positions = pos.get_encoding(seq_len).expect("length checked above");
+            x.add_assign(&positions);
         }
-        for ff in &mut self.ff_layers {
-            ff.zero_grad();
+        let (dropped, embedding_dropout_mask) = self.embedding_dropout.forward(x);
+        x = dropped;
+
+        // Apply each transformer layer
+        let mut blocks = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            let (out, cache) = layer.forward_with_cache(&x, mask);
+            blocks.push(cache);
+            x = out;
+        }
+
+        let hidden = match &self.final_norm {
+            Some(norm) => norm.forward(&x),
+            None => x.clone(),
+        };
+
+        ForwardCache {
+            token_ids: token_ids.to_vec(),
+            blocks,
+            final_norm_input: x,
+            hidden,
+            embedding_dropout_mask,
         }
     }
 
-    /// Applies accumulated gradients to update parameters
-    pub fn apply_grad(&mut self, lr: f32) {
-        self.token_embedding.apply_grad(lr);
-        for attn in &mut self.attention_layers {
-            attn.apply_grad(lr);
+    /// Projects hidden states `[.., hidden_size]` to vocabulary logits through the LM head
+    pub fn logits(&self, hidden: &Tensor) -> Tensor {
+        match &self.lm_head {
+            Some(head) => head.forward(hidden),
+            None => self.token_embedding.project(hidden),
         }
-        for ff in &mut self.ff_layers {
-            ff.apply_grad(lr);
+    }
+
+    /// Backward pass from the gradient of the loss w.r.t. the logits
+    /// (`[seq_len][vocab_size]` or batched), accumulating into every parameter's `grad`
+    pub fn backward(&mut self, cache: &ForwardCache, grad_logits: &Tensor) {
+        let mut grad = match &mut self.lm_head {
+            Some(head) => head.backward(&cache.hidden, grad_logits),
+            None => self
+                .token_embedding
+                .project_backward(&cache.hidden, grad_logits),
+        };
+
+        if let Some(norm) = &mut self.final_norm {
+            grad = norm.backward(&cache.final_norm_input, &grad);
+        }
+
+        for (layer, block_cache) in self.layers.iter_mut().zip(&cache.blocks).rev() {
+            grad = layer.backward(block_cache, &grad);
         }
+
+        // Token and position embeddings are summed, so both receive the same gradient
+        let grad = Dropout::backward(cache.embedding_dropout_mask.as_ref(), grad);
+        if let Some(pos) = &mut self.pos_encoding {
+            pos.backward(&grad);
+        }
+        self.token_embedding.backward(&cache.token_ids, &grad);
     }
 
-    /// Simulates a backward pass by filling dummy gradients
-    pub fn backward(&mut self, token_ids: &[usize]) {
-        self.token_embedding.fill_dummy_grads(token_ids);
-        for attn in &mut self.attention_layers {
-            attn.fill_dummy_grads();
+    /// Splits parameters into optimizer groups for fine-tuning
+    ///
+    /// Biases, norm parameters and the embeddings get zero weight decay;
+    /// everything else uses the optimizer's default. With layer-wise LR decay
+    /// the output head and final norm train at the full rate, layer `i` of
+    /// `n` at `layer_lr_decay^(n - i)` and the embeddings at
+    /// `layer_lr_decay^(n + 1)`; pass `1.0` to disable it.
+    pub fn param_groups(&mut self, layer_lr_decay: f32) -> Vec<ParamGroup<'_>> {
+        let n_layers = self.layers.len();
+        // Depth 0 is the embeddings, 1..=n the layers and n + 1 the output side
+        let mut decay: Vec<Vec<(String, &mut Param)>> =
+            (0..n_layers + 2).map(|_| Vec::new()).collect();
+        let mut no_decay: Vec<Vec<(String, &mut Param)>> =
+            (0..n_layers + 2).map(|_| Vec::new()).collect();
+
+        for (name, param) in self.parameters_mut() {
+            let depth = param_depth(&name, n_layers);
+            if excluded_from_weight_decay(&name) {
+                no_decay[depth].push((name, param));
+            } else {
+                decay[depth].push((name, param));
+            }
         }
-        for ff in &mut self.ff_layers {
-            ff.weight.grad.iter_mut().for_each(|g| *g = 0.001);
-            ff.bias.grad.iter_mut().for_each(|g| *g = 0.001);
+
+        let mut groups = Vec::new();
+        for (depth, (decay, no_decay)) in decay.into_iter().zip(no_decay).enumerate() {
+            let scale = layer_lr_decay.powi((n_layers + 1 - depth) as i32);
+            if !decay.is_empty() {
+                groups.push(ParamGroup::new(decay).with_lr_scale(scale));
+            }
+            if !no_decay.is_empty() {
+                groups.push(
+                    ParamGroup::new(no_decay)
+                        .with_lr_scale(scale)
+                        .with_weight_decay(0.0),
+                );
+            }
         }
+        groups
     }
 }
+
+impl Module for SimpleTransformer {
+    fn set_training(&mut self, training: bool) {
+        self.embedding_dropout.set_training(training);
+        for layer in &mut self.layers {
+            layer.set_training(training);
+        }
+    }
+
+    fn visit_params<'a>(&'a self, prefix: &str, f: &mut dyn FnMut(String, &'a Param)) {
+        self.token_embedding
+            .visit_params(&join(prefix, "token_embedding"), f);
+        if let Some(pos) = &self.pos_encoding {
+            pos.visit_params(&join(prefix, "pos_encoding"), f);
+        }
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer.visit_params(&join(prefix, &format!("layers.{i}")), f);
+        }
+        if let Some(norm) = &self.final_norm {
+            norm.visit_params(&join(prefix, "final_norm"), f);
+        }
+        if let Some(head) = &self.lm_head {
+            head.visit_params(&join(prefix, "lm_head"), f);
+        }
+    }
+
+    fn visit_params_mut<'a>(&'a mut self, prefix: &str, f: &mut dyn FnMut(String, &'a mut Param)) {
+        self.token_embedding
+            .visit_params_mut(&join(prefix, "token_embedding"), f);
+        if let Some(pos) = &mut self.pos_encoding {
+            pos.visit_params_mut(&join(prefix, "pos_encoding"), f);
+        }
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            layer.visit_params_mut(&join(prefix, &format!("layers.{i}")), f);
+        }
+        if let Some(norm) = &mut self.final_norm {
+            norm.visit_params_mut(&join(prefix, "final_norm"), f);
+        }
+        if let Some(head) = &mut self.lm_head {
+            head.visit_params_mut(&join(prefix, "lm_head"), f);
+        }
+    }
+}
+
+/// Depth of a named parameter for layer-wise LR decay: 0 for embeddings,
+/// `i + 1` for `layers.i.*` and `n_layers + 1` for the final norm and head
+fn param_depth(name: &str, n_layers: usize) -> usize {
+    if let Some(rest) = name.strip_prefix("layers.") {
+        let index = rest.split('.').next().and_then(|i| i.parse::<usize>().ok());
+        index.map_or(n_layers + 1, |i| i + 1)
+    } else if name.starts_with("token_embedding.") || name.starts_with("pos_encoding.") {
+        0
+    } else {
+        n_layers + 1
+    }
+}
+
+/// Biases, norm parameters and embeddings are conventionally not decayed
+fn excluded_from_weight_decay(name: &str) -> bool {
+    name.ends_with(".bias")
+        || name.contains("norm.")
+        || name.starts_with("token_embedding.")
+        || name.starts_with("pos_encoding.")
+}
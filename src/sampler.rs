@@ -0,0 +1,287 @@
+use std::fmt;
+
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
+
+use crate::model::init::seeded_rng;
+
+/// One stage of a sampling chain, narrowing or reshaping the candidate tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplingStep {
+    /// Divides every logit by the temperature; below 1 sharpens, above 1 flattens
+    Temperature(f32),
+    /// Keeps the `k` most likely tokens
+    TopK(usize),
+    /// Nucleus sampling: keeps the most likely tokens whose probabilities sum to at least `p`
+    TopP(f32),
+    /// Keeps tokens at least `p` times as likely as the most likely one
+    MinP(f32),
+    /// Locally typical sampling: keeps the tokens whose surprisal is closest to
+    /// the entropy until their probabilities sum to at least `p`
+    Typical(f32),
+}
+
+/// A vocabulary entry still eligible for sampling
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    id: usize,
+    logit: f32,
+}
+
+impl SamplingStep {
+    fn apply(self, candidates: &mut Vec<Candidate>) {
+        match self {
+            SamplingStep::Temperature(t) => candidates.iter_mut().for_each(|c| c.logit /= t),
+            SamplingStep::TopK(k) => {
+                sort_by_logit(candidates);
+                candidates.truncate(k.max(1));
+            }
+            SamplingStep::TopP(p) => {
+                sort_by_logit(candidates);
+                let probs = probabilities(candidates);
+                candidates.truncate(mass_prefix(&probs, p));
+            }
+            SamplingStep::MinP(p) => {
+                let probs = probabilities(candidates);
+                let threshold = p * probs.iter().cloned().fold(0.0, f32::max);
+                let mut keep = probs.iter().map(|&prob| prob >= threshold);
+                candidates.retain(|_| keep.next().unwrap_or(false));
+            }
+            SamplingStep::Typical(p) => {
+                let probs = probabilities(candidates);
+                let entropy: f32 = probs
+                    .iter()
+                    .filter(|&&prob| prob > 0.0)
+                    .map(|prob| -prob * prob.ln())
+                    .sum();
+                let mut ranked: Vec<(Candidate, f32)> =
+                    candidates.iter().copied().zip(probs).collect();
+                ranked.sort_by(|a, b| {
+                    let typicality = |prob: f32| (-prob.ln() - entropy).abs();
+                    typicality(a.1).total_cmp(&typicality(b.1))
+                });
+                let probs: Vec<f32> = ranked.iter().map(|(_, prob)| *prob).collect();
+                let keep = mass_prefix(&probs, p);
+                *candidates = ranked.into_iter().take(keep).map(|(c, _)| c).collect();
+            }
+        }
+    }
+}
+
+/// Picks the next token from logits by running a chain of `SamplingStep`s
+/// and drawing from the softmax of what remains
+///
+/// The chain is applied in the order the steps were added. With a fixed
+/// seed the same logits always produce the same sequence of tokens.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    pub steps: Vec<SamplingStep>,
+    rng: StdRng,
+}
+
+impl Sampler {
+    /// Samples from the full softmax; chain steps with the builder methods
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            steps: Vec::new(),
+            rng: seeded_rng(seed),
+        }
+    }
+
+    /// Always picks the most likely token
+    pub fn greedy() -> Self {
+        Self::new(Some(0)).top_k(1)
+    }
+
+    pub fn with_step(mut self, step: SamplingStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn temperature(self, temperature: f32) -> Self {
+        self.with_step(SamplingStep::Temperature(temperature))
+    }
+
+    pub fn top_k(self, k: usize) -> Self {
+        self.with_step(SamplingStep::TopK(k))
+    }
+
+    pub fn top_p(self, p: f32) -> Self {
+        self.with_step(SamplingStep::TopP(p))
+    }
+
+    pub fn min_p(self, p: f32) -> Self {
+        self.with_step(SamplingStep::MinP(p))
+    }
+
+    pub fn typical(self, p: f32) -> Self {
+        self.with_step(SamplingStep::Typical(p))
+    }
+
+    /// Draws a token ID from `logits` over the vocabulary
+    pub fn sample(&mut self, logits: &[f32]) -> usize {
+        let mut candidates: Vec<Candidate> = logits
+            .iter()
+            .enumerate()
+            .map(|(id, &logit)| Candidate { id, logit })
+            .collect();
+        for step in &self.steps {
+            step.apply(&mut candidates);
+        }
+
+        let probs = probabilities(&candidates);
+        let mut r: f32 = self.rng.random();
+        for (candidate, prob) in candidates.iter().zip(&probs) {
+            if r < *prob {
+                return candidate.id;
+            }
+            r -= prob;
+        }
+        // Rounding can leave a sliver of mass past the last candidate
+        candidates.last().map_or(0, |c| c.id)
+    }
+}
+
+/// Per-request sampling settings, as accepted by the HTTP API
+///
+/// Steps are chained as top-k, typical, top-p, min-p and finally temperature;
+/// unset filters are skipped.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SamplingConfig {
+    /// Softmax temperature; `0.0` (the default) decodes greedily and then
+    /// accepts no filters or seed
+    pub temperature: f32,
+    pub top_k: Option<usize>,
+    pub top_p: Option<f32>,
+    pub min_p: Option<f32>,
+    pub typical_p: Option<f32>,
+    /// Seed of the sampling RNG; `None` seeds it from the OS
+    pub seed: Option<u64>,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            temperature: 0.0,
+            top_k: None,
+            top_p: None,
+            min_p: None,
+            typical_p: None,
+            seed: None,
+        }
+    }
+}
+
+impl SamplingConfig {
+    /// Validates the settings and builds the matching sampler
+    pub fn build(&self) -> Result<Sampler, SamplingError> {
+        if !(self.temperature.is_finite() && self.temperature >= 0.0) {
+            return Err(SamplingError::InvalidTemperature(self.temperature));
+        }
+        if self.temperature == 0.0 {
+            let sampling_fields = [
+                ("top_k", self.top_k.is_some()),
+                ("top_p", self.top_p.is_some()),
+                ("min_p", self.min_p.is_some()),
+                ("typical_p", self.typical_p.is_some()),
+                ("seed", self.seed.is_some()),
+            ];
+            return match sampling_fields.into_iter().find(|&(_, set)| set) {
+                Some((field, _)) => Err(SamplingError::UnusedWhenGreedy(field)),
+                None => Ok(Sampler::greedy()),
+            };
+        }
+        if self.top_k == Some(0) {
+            return Err(SamplingError::InvalidTopK);
+        }
+        for (field, value) in [
+            ("top_p", self.top_p),
+            ("min_p", self.min_p),
+            ("typical_p", self.typical_p),
+        ] {
+            if let Some(p) = value
+                && !(p > 0.0 && p <= 1.0)
+            {
+                return Err(SamplingError::InvalidProbability { field, value: p });
+            }
+        }
+
+        let mut sampler = Sampler::new(self.seed);
+        if let Some(k) = self.top_k {
+            sampler = sampler.top_k(k);
+        }
+        if let Some(p) = self.typical_p {
+            sampler = sampler.typical(p);
+        }
+        if let Some(p) = self.top_p {
+            sampler = sampler.top_p(p);
+        }
+        if let Some(p) = self.min_p {
+            sampler = sampler.min_p(p);
+        }
+        Ok(sampler.temperature(self.temperature))
+    }
+}
+
+/// Reasons a `SamplingConfig` cannot be turned into a sampler
+#[derive(Debug, Clone, PartialEq)]
+pub enum SamplingError {
+    /// Temperature negative or not finite
+    InvalidTemperature(f32),
+    /// `top_k` of zero would leave no candidates
+    InvalidTopK,
+    /// A probability threshold outside `(0, 1]`
+    InvalidProbability { field: &'static str, value: f32 },
+    /// A filter or seed given with temperature 0, which decodes greedily
+    UnusedWhenGreedy(&'static str),
+}
+
+impl fmt::Display for SamplingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SamplingError::InvalidTemperature(t) => {
+                write!(f, "`temperature` must be finite and >= 0, got {t}")
+            }
+            SamplingError::InvalidTopK => write!(f, "`top_k` must be greater than zero"),
+            SamplingError::InvalidProbability { field, value } => {
+                write!(f, "`{field}` must be in (0, 1], got {value}")
+            }
+            SamplingError::UnusedWhenGreedy(field) => write!(
+                f,
+                "`{field}` has no effect with `temperature` 0 (greedy decoding); \
+                 set a temperature above 0 to sample"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SamplingError {}
+
+fn sort_by_logit(candidates: &mut [Candidate]) {
+    candidates.sort_by(|a, b| b.logit.total_cmp(&a.logit));
+}
+
+/// Softmax of the candidate logits
+fn probabilities(candidates: &[Candidate]) -> Vec<f32> {
+    let max = candidates
+        .iter()
+        .map(|c| c.logit)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = candidates.iter().map(|c| (c.logit - max).exp()).collect();
+    let sum: f32 = exps.iter().sum();
+    exps.into_iter().map(|e| e / sum).collect()
+}
+
+/// Length of the shortest prefix of `probs` holding at least `p` of the mass
+fn mass_prefix(probs: &[f32], p: f32) -> usize {
+    let mut total = 0.0;
+    for (i, prob) in probs.iter().enumerate() {
+        total += prob;
+        if total >= p {
+            return i + 1;
+        }
+    }
+    probs.len()
+}
@@ -1,183 +1,94 @@
 use crate::model::{
-    layernorm::LayerNorm,
-    linear::Linear,
-    positional::PositionalEncoding,
-    transformer::{ModelConfig, SimpleTransformer},
+    config::ModelConfig,
+    module::{Module, StateDict},
+    transformer::SimpleTransformer,
 };
-use std::io::{Read, Write};
+use std::io::{Error, ErrorKind, Read, Write};
 
 /// Save the entire model to disk.
+///
+/// The model is written as its named state dict: an entry count followed by
+/// `(name, values)` pairs, all little-endian.
 pub fn save_model<W: Write>(model: &SimpleTransformer, writer: &mut W) -> std::io::Result<()> {
-    save_positional(&model.pos_encoding, writer)?;
-
-    for attn in &model.attention_layers {
-        save_linear(&attn.query_proj, writer)?;
-        save_linear(&attn.key_proj, writer)?;
-        save_linear(&attn.value_proj, writer)?;
-        save_linear(&attn.output_proj, writer)?;
-    }
-
-    for ln in &model.attn_norms {
-        save_layernorm(ln, writer)?;
-    }
-
-    for ff in &model.ff_layers {
-        save_linear(ff, writer)?;
-    }
-
-    for ln in &model.ff_norms {
-        save_layernorm(ln, writer)?;
-    }
-
-    Ok(())
+    save_state_dict(&model.state_dict(), writer)
 }
 
 /// Load the model from disk.
+///
+/// The architecture comes from `config`; the stored parameters must match it
+/// exactly, otherwise an `InvalidData` error naming the offending parameter is
+/// returned.
 pub fn load_model<R: Read>(
     reader: &mut R,
     config: &ModelConfig,
 ) -> std::io::Result<SimpleTransformer> {
-    use crate::model::attention::MultiHeadAttention;
-
-    let pos_encoding = load_positional(reader, config.max_position_embeddings, config.hidden_size)?;
-
-    let mut attention_layers = Vec::with_capacity(config.num_layers);
-    for _ in 0..config.num_layers {
-        let query_proj = load_linear(reader, config.hidden_size, config.hidden_size)?;
-        let key_proj = load_linear(reader, config.hidden_size, config.hidden_size)?;
-        let value_proj = load_linear(reader, config.hidden_size, config.hidden_size)?;
-        let output_proj = load_linear(reader, config.hidden_size, config.hidden_size)?;
-
-        attention_layers.push(MultiHeadAttention {
-            num_heads: config.num_heads,
-            head_dim: config.hidden_size / config.num_heads,
-            query_proj,
-            key_proj,
-            value_proj,
-            output_proj,
-        });
-    }
-
-    let mut attn_norms = Vec::with_capacity(config.num_layers);
-    for _ in 0..config.num_layers {
-        attn_norms.push(load_layernorm(reader, config.hidden_size)?);
-    }
-
-    let mut ff_layers = Vec::with_capacity(config.num_layers);
-    for _ in 0..config.num_layers {
-        ff_layers.push(load_linear(reader, config.hidden_size, config.hidden_size)?);
-    }
-
-    let mut ff_norms = Vec::with_capacity(config.num_layers);
-    for _ in 0..config.num_layers {
-        ff_norms.push(load_layernorm(reader, config.hidden_size)?);
-    }
-
-    Ok(SimpleTransformer {
-        hidden_size: config.hidden_size,
-        pos_encoding,
-        attention_layers,
-        attn_norms,
-        ff_layers,
-        ff_norms,
-    })
+    let mut model = SimpleTransformer::from_config(config)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    let state = load_state_dict(reader)?;
+    model
+        .load_state_dict(&state)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    Ok(model)
 }
 
-/// Serialize a Linear layer to a writer.
-pub fn save_linear<W: Write>(linear: &Linear, writer: &mut W) -> std::io::Result<()> {
-    for w in &linear.weights {
-        writer.write_all(&w.to_le_bytes())?;
-    }
-    for b in &linear.biases {
-        writer.write_all(&b.to_le_bytes())?;
-    }
-    Ok(())
-}
-
-/// Load a Linear layer from a reader.
-pub fn load_linear<R: Read>(
-    reader: &mut R,
-    input_dim: usize,
-    output_dim: usize,
-) -> std::io::Result<Linear> {
-    let mut weights = vec![0.0; input_dim * output_dim];
-    let mut biases = vec![0.0; output_dim];
-
-    for w in &mut weights {
-        *w = read_f32(reader)?;
-    }
-    for b in &mut biases {
-        *b = read_f32(reader)?;
-    }
-
-    Ok(Linear {
-        weights,
-        biases,
-        input_dim,
-        output_dim,
-    })
-}
-
-/// Serialize a LayerNorm layer.
-pub fn save_layernorm<W: Write>(ln: &LayerNorm, writer: &mut W) -> std::io::Result<()> {
-    for g in &ln.gamma {
-        writer.write_all(&g.to_le_bytes())?;
-    }
-    for b in &ln.beta {
-        writer.write_all(&b.to_le_bytes())?;
+/// Serialize a state dict to a writer.
+pub fn save_state_dict<W: Write>(state: &StateDict, writer: &mut W) -> std::io::Result<()> {
+    write_u64(writer, state.len())?;
+    for (name, values) in state {
+        write_u64(writer, name.len())?;
+        writer.write_all(name.as_bytes())?;
+        write_u64(writer, values.len())?;
+        for v in values {
+            writer.write_all(&v.to_le_bytes())?;
+        }
     }
     Ok(())
 }
 
-/// Load a LayerNorm layer.
-pub fn load_layernorm<R: Read>(reader: &mut R, dim: usize) -> std::io::Result<LayerNorm> {
-    let mut gamma = vec![0.0; dim];
-    let mut beta = vec![0.0; dim];
-
-    for g in &mut gamma {
-        *g = read_f32(reader)?;
-    }
-    for b in &mut beta {
-        *b = read_f32(reader)?;
-    }
-
-    Ok(LayerNorm {
-        gamma,
-        beta,
-        epsilon: 1e-5,
-    })
+/// Load a state dict written by `save_state_dict`.
+pub fn load_state_dict<R: Read>(reader: &mut R) -> std::io::Result<StateDict> {
+    let count = read_u64(reader)?;
+    let mut state = StateDict::new();
+    for _ in 0..count {
+        let len = read_u64(reader)?;
+        let name = read_bytes(reader, len)?;
+        let name = String::from_utf8(name).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        let len = read_u64(reader)?;
+        let byte_len = len
+            .checked_mul(size_of::<f32>())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "parameter length overflows"))?;
+        let values = read_bytes(reader, byte_len)?
+            .chunks_exact(size_of::<f32>())
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        state.insert(name, values);
+    }
+    Ok(state)
 }
 
-/// Serialize a PositionalEncoding.
-pub fn save_positional<W: Write>(pos: &PositionalEncoding, writer: &mut W) -> std::io::Result<()> {
-    for vec in &pos.embeddings {
-        for v in vec {
-            writer.write_all(&v.to_le_bytes())?;
-        }
-    }
-    Ok(())
+/// Read exactly `len` bytes without trusting `len` for the allocation, so a
+/// corrupt length fails with `UnexpectedEof` instead of exhausting memory.
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader.by_ref().take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("expected {len} bytes, found {}", buf.len()),
+        ));
+    }
+    Ok(buf)
 }
 
-/// Load a PositionalEncoding.
-pub fn load_positional<R: Read>(
-    reader: &mut R,
-    max_pos: usize,
-    dim: usize,
-) -> std::io::Result<PositionalEncoding> {
-    let mut embeddings = Vec::with_capacity(max_pos);
-    for _ in 0..max_pos {
-        let mut row = Vec::with_capacity(dim);
-        for _ in 0..dim {
-            row.push(read_f32(reader)?);
-        }
-        embeddings.push(row);
-    }
-    Ok(PositionalEncoding { embeddings })
+/// Write a length as a little-endian u64.
+pub(crate) fn write_u64<W: Write>(writer: &mut W, value: usize) -> std::io::Result<()> {
+    writer.write_all(&(value as u64).to_le_bytes())
 }
 
-/// Read a single f32 from reader.
-fn read_f32<R: Read>(reader: &mut R) -> std::io::Result<f32> {
-    let mut buf = [0u8; 4];
+/// Read a length written by `write_u64`.
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<usize> {
+    let mut buf = [0u8; 8];
     reader.read_exact(&mut buf)?;
-    Ok(f32::from_le_bytes(buf))
+    usize::try_from(u64::from_le_bytes(buf)).map_err(|e| Error::new(ErrorKind::InvalidData, e))
 }
@@ -1,7 +1,16 @@
 use std::collections::HashMap;
 
+/// Token ID reserved for `<pad>`
+pub const PAD_TOKEN_ID: usize = 0;
+/// Token ID reserved for `<unk>`
+pub const UNK_TOKEN_ID: usize = 1;
+/// Token ID reserved for `<eos>`, marking the end of a generated sequence
+pub const EOS_TOKEN_ID: usize = 2;
+
 pub struct Tokenizer {
     vocab: HashMap<String, usize>,
+    /// Inverse of `vocab`, indexed by token ID
+    tokens: Vec<String>,
 }
 
 impl Default for Tokenizer {
@@ -12,23 +21,38 @@ impl Default for Tokenizer {
 
 impl Tokenizer {
     pub fn new() -> Self {
-        let mut vocab = std::collections::HashMap::new();
-        vocab.insert("<pad>".to_string(), 0);
-        vocab.insert("<unk>".to_string(), 1);
-        Self { vocab }
+        let mut tokenizer = Self {
+            vocab: HashMap::new(),
+            tokens: Vec::new(),
+        };
+        tokenizer.register_tokens(&["<pad>", "<unk>", "<eos>"]);
+        tokenizer
     }
 
     pub fn tokenize(&self, text: &str) -> Vec<usize> {
         text.split_whitespace()
-            .map(|word| *self.vocab.get(word).unwrap_or(&1)) // unknown = 1
+            .map(|word| *self.vocab.get(word).unwrap_or(&UNK_TOKEN_ID))
             .collect()
     }
 
+    /// Maps token IDs back to text, joining words with single spaces
+    ///
+    /// IDs outside the vocabulary decode as `<unk>`.
+    pub fn decode(&self, token_ids: &[usize]) -> String {
+        token_ids
+            .iter()
+            .map(|&id| self.tokens.get(id).map_or("<unk>", String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     /// Registers new words into the vocabulary
     pub fn register_tokens(&mut self, tokens: &[&str]) {
         for token in tokens {
-            let next_index = self.vocab.len();
-            self.vocab.entry(token.to_string()).or_insert(next_index);
+            if !self.vocab.contains_key(*token) {
+                self.vocab.insert(token.to_string(), self.tokens.len());
+                self.tokens.push(token.to_string());
+            }
         }
     }
 
@@ -37,7 +61,7 @@ impl Tokenizer {
     pub fn pad_sequences(&self, sequences: &mut Vec<Vec<usize>>, max_length: usize) {
         for seq in sequences.iter_mut() {
             while seq.len() < max_length {
-                seq.push(0); // Add <pad> token (ID 0) for padding
+                seq.push(PAD_TOKEN_ID);
             }
         }
     }
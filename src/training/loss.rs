@@ -1,3 +1,5 @@
+use crate::model::tensor::Tensor;
+
 /// Mean Squared Error loss
 pub fn mse_loss(output: &[f32], target: &[f32]) -> f32 {
     output
@@ -7,3 +9,63 @@ pub fn mse_loss(output: &[f32], target: &[f32]) -> f32 {
         .sum::<f32>()
         / output.len() as f32
 }
+
+/// Softmax cross-entropy over per-position vocabulary logits `[.., vocab_size]`
+///
+/// `targets` holds one token per logits row, in row-major order.
+/// Returns the mean loss over all positions whose target is not
+/// `ignore_index`, together with its gradient w.r.t. `logits` (ignored
+/// positions get a zero gradient). With `label_smoothing = α` the target
+/// distribution is `(1 - α)·one_hot + α / vocab_size`.
+pub fn cross_entropy_loss(
+    logits: &Tensor,
+    targets: &[usize],
+    ignore_index: Option<usize>,
+    label_smoothing: f32,
+) -> (f32, Tensor) {
+    assert_eq!(logits.num_rows(), targets.len());
+    assert!(
+        (0.0..1.0).contains(&label_smoothing),
+        "label_smoothing must be in [0, 1), got {label_smoothing}"
+    );
+
+    let counted = targets.iter().filter(|&&t| Some(t) != ignore_index).count();
+    let mut grads = Tensor::zeros(logits.shape());
+    if counted == 0 {
+        return (0.0, grads);
+    }
+    let norm = 1.0 / counted as f32;
+
+    let mut total = 0.0;
+    for ((row, &target), grad) in logits.rows().zip(targets).zip(grads.rows_mut()) {
+        if Some(target) == ignore_index {
+            continue;
+        }
+        let vocab_size = row.len() as f32;
+        let log_probs = log_softmax(row);
+        let smooth = label_smoothing / vocab_size;
+
+        let nll = -log_probs[target];
+        let mean_nll = -log_probs.iter().sum::<f32>() / vocab_size;
+        total += (1.0 - label_smoothing) * nll + label_smoothing * mean_nll;
+
+        // d/dz of -Σ q·log softmax(z) is softmax(z) - q
+        for (k, (g, lp)) in grad.iter_mut().zip(&log_probs).enumerate() {
+            let q = if k == target {
+                1.0 - label_smoothing + smooth
+            } else {
+                smooth
+            };
+            *g = (lp.exp() - q) * norm;
+        }
+    }
+
+    (total * norm, grads)
+}
+
+/// Log-softmax computed with the log-sum-exp trick
+pub fn log_softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let log_sum_exp = max + logits.iter().map(|l| (l - max).exp()).sum::<f32>().ln();
+    logits.iter().map(|l| l - log_sum_exp).collect()
+}
@@ -1,15 +1,261 @@
-/// Simplified SGD Optimizer placeholder
+use std::collections::HashMap;
+
+use crate::model::common::Param;
+
+/// A set of named parameters sharing optimizer hyperparameters
+pub struct ParamGroup<'a> {
+    pub params: Vec<(String, &'a mut Param)>,
+    /// Multiplier applied to the optimizer's learning rate
+    pub lr_scale: f32,
+    /// Weight decay for this group; `None` uses the optimizer's own setting
+    pub weight_decay: Option<f32>,
+}
+
+impl<'a> ParamGroup<'a> {
+    /// A group using the optimizer defaults
+    pub fn new(params: Vec<(String, &'a mut Param)>) -> Self {
+        Self {
+            params,
+            lr_scale: 1.0,
+            weight_decay: None,
+        }
+    }
+
+    pub fn with_lr_scale(mut self, lr_scale: f32) -> Self {
+        self.lr_scale = lr_scale;
+        self
+    }
+
+    pub fn with_weight_decay(mut self, weight_decay: f32) -> Self {
+        self.weight_decay = Some(weight_decay);
+        self
+    }
+}
+
+/// Updates model parameters from their accumulated gradients
+///
+/// Per-parameter state such as momentum buffers is keyed by the names from
+/// `Module::parameters_mut`, so `step` and `step_groups` can be mixed and the
+/// parameters may come in any order.
+pub trait Optimizer {
+    /// Current learning rate
+    fn learning_rate(&self) -> f32;
+
+    /// Overrides the learning rate, e.g. from a schedule
+    fn set_learning_rate(&mut self, lr: f32);
+
+    /// Default weight decay for parameters without a group override
+    fn weight_decay(&self) -> f32;
+
+    /// Called once at the start of every step, before any `update`
+    fn begin_step(&mut self) {}
+
+    /// Updates a single parameter; `name` identifies its state across steps
+    fn update(&mut self, name: &str, param: &mut Param, lr: f32, weight_decay: f32);
+
+    /// Applies one update to every named parameter with the default hyperparameters
+    fn step(&mut self, params: &mut [(String, &mut Param)]) {
+        self.begin_step();
+        let (lr, weight_decay) = (self.learning_rate(), self.weight_decay());
+        for (name, param) in params.iter_mut() {
+            self.update(name, param, lr, weight_decay);
+        }
+    }
+
+    /// Applies one update to every group, honouring its learning-rate scale
+    /// and weight-decay override
+    fn step_groups(&mut self, groups: &mut [ParamGroup]) {
+        self.begin_step();
+        for group in groups.iter_mut() {
+            let lr = self.learning_rate() * group.lr_scale;
+            let weight_decay = group.weight_decay.unwrap_or(self.weight_decay());
+            for (name, param) in group.params.iter_mut() {
+                self.update(name, param, lr, weight_decay);
+            }
+        }
+    }
+}
+
+/// Stochastic gradient descent with optional momentum, Nesterov momentum
+/// and L2 weight decay
 pub struct SGD {
     pub lr: f32,
+    pub momentum: f32,
+    pub nesterov: bool,
+    pub weight_decay: f32,
+    velocity: HashMap<String, Vec<f32>>,
 }
 
 impl SGD {
     pub fn new(lr: f32) -> Self {
-        Self { lr }
+        Self {
+            lr,
+            momentum: 0.0,
+            nesterov: false,
+            weight_decay: 0.0,
+            velocity: HashMap::new(),
+        }
+    }
+
+    /// Enables (Nesterov) momentum with coefficient `momentum`
+    pub fn with_momentum(mut self, momentum: f32, nesterov: bool) -> Self {
+        self.momentum = momentum;
+        self.nesterov = nesterov;
+        self
+    }
+
+    /// Adds `weight_decay · w` to every gradient
+    pub fn with_weight_decay(mut self, weight_decay: f32) -> Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+}
+
+impl Optimizer for SGD {
+    fn learning_rate(&self) -> f32 {
+        self.lr
+    }
+
+    fn set_learning_rate(&mut self, lr: f32) {
+        self.lr = lr;
+    }
+
+    fn weight_decay(&self) -> f32 {
+        self.weight_decay
+    }
+
+    fn update(&mut self, name: &str, param: &mut Param, lr: f32, weight_decay: f32) {
+        let velocity = state_for(&mut self.velocity, name, param.value.len());
+        for ((w, g), buf) in param
+            .value
+            .iter_mut()
+            .zip(&param.grad)
+            .zip(velocity.iter_mut())
+        {
+            let grad = g + weight_decay * *w;
+            let update = if self.momentum > 0.0 {
+                *buf = self.momentum * *buf + grad;
+                if self.nesterov {
+                    grad + self.momentum * *buf
+                } else {
+                    *buf
+                }
+            } else {
+                grad
+            };
+            *w -= lr * update;
+        }
+    }
+}
+
+/// Adam with bias-corrected first/second moments
+///
+/// With `decoupled_weight_decay` (AdamW) the decay is applied directly to the
+/// weights; otherwise it is added to the gradient as L2 regularization.
+pub struct Adam {
+    pub lr: f32,
+    pub beta1: f32,
+    pub beta2: f32,
+    pub epsilon: f32,
+    pub weight_decay: f32,
+    pub decoupled_weight_decay: bool,
+    step: i32,
+    first_moment: HashMap<String, Vec<f32>>,
+    second_moment: HashMap<String, Vec<f32>>,
+}
+
+impl Adam {
+    pub fn new(lr: f32) -> Self {
+        Self {
+            lr,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+            weight_decay: 0.0,
+            decoupled_weight_decay: false,
+            step: 0,
+            first_moment: HashMap::new(),
+            second_moment: HashMap::new(),
+        }
+    }
+
+    /// AdamW: Adam with decoupled weight decay
+    pub fn adamw(lr: f32, weight_decay: f32) -> Self {
+        Self {
+            weight_decay,
+            decoupled_weight_decay: true,
+            ..Self::new(lr)
+        }
+    }
+
+    pub fn with_betas(mut self, beta1: f32, beta2: f32) -> Self {
+        self.beta1 = beta1;
+        self.beta2 = beta2;
+        self
     }
 
-    /// Apply dummy step — just log for now
-    pub fn step(&self) {
-        println!("[SGD] Step taken with learning rate {}", self.lr);
+    /// Number of steps taken so far
+    pub fn steps(&self) -> i32 {
+        self.step
+    }
+}
+
+impl Optimizer for Adam {
+    fn learning_rate(&self) -> f32 {
+        self.lr
+    }
+
+    fn set_learning_rate(&mut self, lr: f32) {
+        self.lr = lr;
+    }
+
+    fn weight_decay(&self) -> f32 {
+        self.weight_decay
+    }
+
+    fn begin_step(&mut self) {
+        self.step += 1;
+    }
+
+    fn update(&mut self, name: &str, param: &mut Param, lr: f32, weight_decay: f32) {
+        let bias_correction1 = 1.0 - self.beta1.powi(self.step);
+        let bias_correction2 = 1.0 - self.beta2.powi(self.step);
+
+        let len = param.value.len();
+        let m = state_for(&mut self.first_moment, name, len);
+        let v = state_for(&mut self.second_moment, name, len);
+        for (((w, g), m), v) in param
+            .value
+            .iter_mut()
+            .zip(&param.grad)
+            .zip(m.iter_mut())
+            .zip(v.iter_mut())
+        {
+            let grad = if self.decoupled_weight_decay {
+                *w -= lr * weight_decay * *w;
+                *g
+            } else {
+                g + weight_decay * *w
+            };
+            *m = self.beta1 * *m + (1.0 - self.beta1) * grad;
+            *v = self.beta2 * *v + (1.0 - self.beta2) * grad * grad;
+            let m_hat = *m / bias_correction1;
+            let v_hat = *v / bias_correction2;
+            *w -= lr * m_hat / (v_hat.sqrt() + self.epsilon);
+        }
+    }
+}
+
+/// Returns the state buffer of parameter `name`, (re)initializing it with
+/// zeros when it does not exist yet or the parameter was resized
+fn state_for<'a>(
+    states: &'a mut HashMap<String, Vec<f32>>,
+    name: &str,
+    len: usize,
+) -> &'a mut Vec<f32> {
+    let state = states.entry(name.to_string()).or_default();
+    if state.len() != len {
+        *state = vec![0.0; len];
     }
+    state
 }
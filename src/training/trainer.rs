@@ -1,18 +1,32 @@
+use crate::model::config::ModelConfig;
+use crate::model::module::Module;
 use crate::model::transformer::SimpleTransformer;
-use crate::tokenizer::Tokenizer;
-use crate::training::consts::{EPOCHS, LEARNING_RATE};
+use crate::tokenizer::PAD_TOKEN_ID;
+use crate::training::consts::EPOCHS;
+use crate::training::loss::cross_entropy_loss;
+use crate::training::optimizer::Optimizer;
 
 use crate::utils::checkpointing::{load_checkpoint, save_checkpoint};
 use crate::utils::distributed::aggregate_gradients_distributed;
-use crate::utils::gradient_clipping::clip_gradients;
+use crate::utils::gradient_clipping::clip_grad_norm;
 use crate::utils::lr_scheduler::adjust_learning_rate;
 
 pub struct Trainer {
+    /// Peak learning rate fed to the warmup/decay schedule
     pub lr: f32,
     pub clip_value: f32,
     pub warmup_steps: usize,
     pub total_steps: usize,
     pub num_workers: usize,
+    /// Label smoothing applied to the cross-entropy targets
+    pub label_smoothing: f32,
+    /// Update rule applied after each backward pass (e.g. `SGD`, `Adam::adamw`)
+    pub optimizer: Box<dyn Optimizer>,
+    /// Per-layer learning-rate decay for `SimpleTransformer::param_groups`; 1.0 disables it
+    pub layer_lr_decay: f32,
+    /// Sequences per optimizer step; each batch must be padded to one length
+    pub batch_size: usize,
+    pub checkpoint_path: String,
 }
 
 impl Trainer {
@@ -23,32 +37,53 @@ impl Trainer {
         data: &[Vec<usize>],
         targets: &[Vec<usize>],
     ) {
+        // A model left in eval mode would otherwise train without dropout
+        model.train();
         let mut step = 0;
 
-        for epoch in 0..10 {
+        for epoch in 0..EPOCHS {
             println!("Epoch {epoch}");
 
-            for (input, target) in data.iter().zip(targets.iter()) {
+            for (input, target) in data
+                .chunks(self.batch_size.max(1))
+                .zip(targets.chunks(self.batch_size.max(1)))
+            {
                 step += 1;
 
                 // Adjust learning rate with warmup/decay schedule
                 let lr = adjust_learning_rate(self.lr, step, self.warmup_steps, self.total_steps);
 
-                // Forward pass and compute loss
-                let _output = model.forward(input); // Replace with actual loss computation
-                let loss = model.mse_loss(target); // Implement mse_loss in model
+                // Forward pass and next-token loss; padding does not contribute
+                let mask = model.default_batch_mask(input);
+                let (logits, cache) = model.forward_batch_with_cache(input, Some(&mask));
+                let (loss, grad_logits) = cross_entropy_loss(
+                    &logits,
+                    &target.concat(),
+                    Some(PAD_TOKEN_ID),
+                    self.label_smoothing,
+                );
 
-                // Backward pass (returns gradients of all parameters as flat Vec<f32>)
-                let mut grads = model.backward();
+                // Backward pass accumulates gradients into every parameter
+                model.zero_grad();
+                model.backward(&cache, &grad_logits);
 
-                // Clip gradients by global norm
-                clip_gradients(&mut grads, self.clip_value);
+                {
+                    let mut params: Vec<_> =
+                        model.parameters_mut().into_iter().map(|(_, p)| p).collect();
 
-                // Aggregate gradients across workers (simulated)
-                aggregate_gradients_distributed(&mut grads, self.num_workers);
+                    // Clip gradients by global norm
+                    clip_grad_norm(&mut params, self.clip_value);
 
-                // Update parameters
-                model.apply_grads(&grads, lr);
+                    // Aggregate gradients across workers (simulated)
+                    for param in params.iter_mut() {
+                        aggregate_gradients_distributed(&mut param.grad, self.num_workers);
+                    }
+                }
+
+                // Update parameters, without decay on biases/norms/embeddings
+                let mut groups = model.param_groups(self.layer_lr_decay);
+                self.optimizer.set_learning_rate(lr);
+                self.optimizer.step_groups(&mut groups);
 
                 // Save periodic checkpoints
                 if step % 100 == 0 {
@@ -63,9 +98,9 @@ impl Trainer {
         }
     }
 
-    /// Resumes training from checkpoint
-    pub fn resume_training(&mut self) -> Option<(SimpleTransformer, usize)> {
-        if let Some(checkpoint) = load_checkpoint(&self.checkpoint_path) {
+    /// Resumes training from checkpoint; `config` must describe the saved model
+    pub fn resume_training(&mut self, config: &ModelConfig) -> Option<(SimpleTransformer, usize)> {
+        if let Some(checkpoint) = load_checkpoint(&self.checkpoint_path, config) {
             println!("Resumed from checkpoint: epoch {}", checkpoint.epoch);
             Some((checkpoint.model, checkpoint.epoch))
         } else {
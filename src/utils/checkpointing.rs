@@ -1,9 +1,9 @@
+use crate::model::config::ModelConfig;
 use crate::model::transformer::SimpleTransformer;
-use serde::{Deserialize, Serialize};
+use crate::serialization::{load_model, read_u64, save_model, write_u64};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufReader, BufWriter, Write};
 
-#[derive(Serialize, Deserialize)]
 pub struct Checkpoint {
     pub model: SimpleTransformer,
     pub epoch: usize,
@@ -11,18 +11,18 @@ pub struct Checkpoint {
 
 /// Saves a checkpoint of the model and training state to disk
 pub fn save_checkpoint(model: &SimpleTransformer, epoch: usize, path: &str) {
-    let checkpoint = Checkpoint {
-        model: model.clone(),
-        epoch,
-    };
     let file = File::create(path).expect("Failed to create checkpoint file");
-    bincode::serialize_into(file, &checkpoint).expect("Failed to write checkpoint");
+    let mut writer = BufWriter::new(file);
+    write_u64(&mut writer, epoch)
+        .and_then(|_| save_model(model, &mut writer))
+        .and_then(|_| writer.flush())
+        .expect("Failed to write checkpoint");
 }
 
-/// Loads a checkpoint from disk
-pub fn load_checkpoint(path: &str) -> Option<Checkpoint> {
-    let mut file = File::open(path).ok()?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer).ok()?;
-    bincode::deserialize(&buffer).ok()
+/// Loads a checkpoint from disk into a model built from `config`
+pub fn load_checkpoint(path: &str, config: &ModelConfig) -> Option<Checkpoint> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let epoch = read_u64(&mut reader).ok()?;
+    let model = load_model(&mut reader, config).ok()?;
+    Some(Checkpoint { model, epoch })
 }
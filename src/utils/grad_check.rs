@@ -0,0 +1,198 @@
+use crate::model::common::Param;
+use crate::model::module::Module;
+
+/// Settings for finite-difference gradient checking
+#[derive(Debug, Clone)]
+pub struct GradCheckConfig {
+    /// Step used for the central difference `(L(w + ε) - L(w - ε)) / 2ε`
+    pub epsilon: f32,
+    /// Maximum allowed `|analytic - numerical| / max(|analytic|, |numerical|)`
+    pub rel_tolerance: f64,
+    /// Absolute differences below this always pass, so near-zero gradients
+    /// are not judged by an unstable relative error
+    pub abs_tolerance: f64,
+    /// Upper bound on checked entries per parameter, spread evenly over it
+    pub max_entries: usize,
+}
+
+impl Default for GradCheckConfig {
+    fn default() -> Self {
+        Self {
+            epsilon: 1e-2,
+            rel_tolerance: 1e-2,
+            abs_tolerance: 1e-3,
+            max_entries: 32,
+        }
+    }
+}
+
+/// A single parameter entry whose gradients disagree
+#[derive(Debug, Clone)]
+pub struct GradMismatch {
+    pub index: usize,
+    pub analytic: f64,
+    pub numerical: f64,
+    pub rel_error: f64,
+}
+
+/// Outcome of checking one parameter
+#[derive(Debug, Clone, Default)]
+pub struct GradCheckReport {
+    pub name: String,
+    pub checked: usize,
+    pub max_rel_error: f64,
+    pub mismatches: Vec<GradMismatch>,
+}
+
+impl GradCheckReport {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Accessor that borrows one parameter out of a model
+pub type ParamAccessor<M> = for<'a> fn(&'a mut M) -> &'a mut Param;
+
+/// Compares the analytic gradient stored in a parameter's `grad` against a
+/// central finite difference of `loss`
+///
+/// `grad` must already hold the gradient of `loss` at the current values,
+/// i.e. the caller runs forward + backward first. Every perturbed value is
+/// restored before returning.
+pub fn check_param<M, L>(
+    model: &mut M,
+    name: &str,
+    param: ParamAccessor<M>,
+    loss: L,
+    config: &GradCheckConfig,
+) -> GradCheckReport
+where
+    L: Fn(&M) -> f64,
+{
+    check_with(model, name, |m| param(m), loss, config)
+}
+
+/// Shared implementation of `check_param` for any way of reaching a parameter
+fn check_with<M, P, L>(
+    model: &mut M,
+    name: &str,
+    param: P,
+    loss: L,
+    config: &GradCheckConfig,
+) -> GradCheckReport
+where
+    P: Fn(&mut M) -> &mut Param,
+    L: Fn(&M) -> f64,
+{
+    let len = param(model).value.len();
+    let stride = len.div_ceil(config.max_entries.max(1)).max(1);
+    let eps = config.epsilon;
+    let mut report = GradCheckReport {
+        name: name.to_string(),
+        ..Default::default()
+    };
+
+    for index in (0..len).step_by(stride) {
+        let analytic = param(model).grad[index] as f64;
+        let original = param(model).value[index];
+
+        param(model).value[index] = original + eps;
+        let plus = loss(model);
+        param(model).value[index] = original - eps;
+        let minus = loss(model);
+        param(model).value[index] = original;
+
+        let numerical = (plus - minus) / (2.0 * eps as f64);
+        let abs_error = (analytic - numerical).abs();
+        let scale = analytic.abs().max(numerical.abs());
+        let rel_error = if scale > 0.0 { abs_error / scale } else { 0.0 };
+
+        report.checked += 1;
+        if abs_error > config.abs_tolerance {
+            report.max_rel_error = report.max_rel_error.max(rel_error);
+            if rel_error > config.rel_tolerance {
+                report.mismatches.push(GradMismatch {
+                    index,
+                    analytic,
+                    numerical,
+                    rel_error,
+                });
+            }
+        }
+    }
+
+    report
+}
+
+/// Runs `check_param` for every named accessor
+pub fn check_params<M, L>(
+    model: &mut M,
+    params: &[(&str, ParamAccessor<M>)],
+    loss: L,
+    config: &GradCheckConfig,
+) -> Vec<GradCheckReport>
+where
+    L: Fn(&M) -> f64,
+{
+    params
+        .iter()
+        .map(|(name, param)| check_param(model, name, *param, &loss, config))
+        .collect()
+}
+
+/// Runs `check_param` for every parameter of a module, reported under its
+/// hierarchical name
+pub fn check_module<M, L>(model: &mut M, loss: L, config: &GradCheckConfig) -> Vec<GradCheckReport>
+where
+    M: Module,
+    L: Fn(&M) -> f64,
+{
+    let names: Vec<String> = model
+        .parameters()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            check_with(
+                model,
+                name,
+                |m| m.parameters_mut().swap_remove(index).1,
+                &loss,
+                config,
+            )
+        })
+        .collect()
+}
+
+/// Panics with a readable summary if any report contains mismatches
+pub fn assert_gradients_match(reports: &[GradCheckReport]) {
+    let failed: Vec<String> = reports
+        .iter()
+        .filter(|r| !r.passed())
+        .map(|r| {
+            let worst = r
+                .mismatches
+                .iter()
+                .max_by(|a, b| a.rel_error.total_cmp(&b.rel_error))
+                .expect("failed report has mismatches");
+            format!(
+                "{}: {}/{} entries differ, worst at [{}] analytic={} numerical={} (rel {:.3e})",
+                r.name,
+                r.mismatches.len(),
+                r.checked,
+                worst.index,
+                worst.analytic,
+                worst.numerical,
+                worst.rel_error
+            )
+        })
+        .collect();
+    assert!(
+        failed.is_empty(),
+        "gradient check failed:\n{}",
+        failed.join("\n")
+    );
+}
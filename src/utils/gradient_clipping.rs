@@ -1,3 +1,5 @@
+use crate::model::common::Param;
+
 /// Clips gradients based on global norm to stabilize training
 pub fn clip_gradients(grads: &mut [f32], clip_value: f32) {
     let total_norm: f32 = grads.iter().map(|g| g * g).sum::<f32>().sqrt();
@@ -8,3 +10,21 @@ pub fn clip_gradients(grads: &mut [f32], clip_value: f32) {
         }
     }
 }
+
+/// Clips the gradients of all `params` by their combined global norm and
+/// returns the norm measured before clipping
+pub fn clip_grad_norm(params: &mut [&mut Param], max_norm: f32) -> f32 {
+    let total_norm = params
+        .iter()
+        .flat_map(|p| p.grad.iter())
+        .map(|g| g * g)
+        .sum::<f32>()
+        .sqrt();
+    if total_norm > max_norm {
+        let scale = max_norm / total_norm;
+        for p in params.iter_mut() {
+            p.grad.iter_mut().for_each(|g| *g *= scale);
+        }
+    }
+    total_norm
+}
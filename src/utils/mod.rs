@@ -2,6 +2,7 @@ pub mod checkpointing;
 pub mod distributed;
 pub mod efficient_attention;
 pub mod fp16;
+pub mod grad_check;
 pub mod gradient_clipping;
 pub mod lr_scheduler;
 pub mod quantization;
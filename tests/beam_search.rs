@@ -0,0 +1,158 @@
+mod common;
+
+use llm_engine::beam_search::{BeamSearchConfig, Hypothesis, beam_search};
+use llm_engine::inference::{GenerationConfig, InferenceEngine};
+use llm_engine::model::config::ModelConfig;
+use llm_engine::model::init::Init;
+use llm_engine::model::layers::positional::SequenceTooLong;
+use llm_engine::model::transformer::SimpleTransformer;
+use llm_engine::sampler::Sampler;
+use llm_engine::tokenizer::{EOS_TOKEN_ID, PAD_TOKEN_ID, Tokenizer};
+
+const PROMPT: [usize; 3] = [3, 4, 5];
+
+fn config() -> ModelConfig {
+    ModelConfig {
+        d_model: 16,
+        max_seq_len: 16,
+        vocab_size: 9,
+        ff_hidden_size: 16,
+        init: Init::Normal { std: 0.5 },
+        ..common::small_config()
+    }
+}
+
+/// A model that ends sequences often enough for hypotheses to finish early
+fn model() -> SimpleTransformer {
+    let mut model = SimpleTransformer::from_config(&config()).unwrap();
+    model.lm_head.as_mut().unwrap().bias.value[EOS_TOKEN_ID] = 2.5;
+    model
+}
+
+/// Log-probabilities of the token after `sequence`, recomputed from scratch
+fn next_log_probs(model: &SimpleTransformer, sequence: &[usize]) -> Vec<f32> {
+    let logits = model.forward(sequence);
+    let mut last = logits.row(logits.num_rows() - 1).to_vec();
+    last[PAD_TOKEN_ID] = f32::NEG_INFINITY;
+    let max = last.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let log_sum = last.iter().map(|l| (l - max).exp()).sum::<f32>().ln() + max;
+    last.iter().map(|l| l - log_sum).collect()
+}
+
+/// Whether `hypothesis` ended with the end-of-sequence token rather than
+/// running out of steps
+fn ended_by_eos(hypothesis: &Hypothesis, config: &BeamSearchConfig) -> bool {
+    hypothesis.token_ids.len() < config.max_new_tokens
+}
+
+#[test]
+fn width_one_matches_greedy_decoding() {
+    let config = BeamSearchConfig {
+        beam_width: 1,
+        max_new_tokens: 8,
+        eos_token_id: None,
+        ..BeamSearchConfig::default()
+    };
+    let hypotheses = beam_search(&model(), &PROMPT, &config).unwrap();
+
+    let engine = InferenceEngine::new(model(), Tokenizer::new());
+    let greedy = engine
+        .generate_ids(
+            &PROMPT,
+            &GenerationConfig {
+                max_new_tokens: 8,
+                eos_token_id: None,
+            },
+            &mut Sampler::greedy(),
+        )
+        .unwrap();
+    assert_eq!(hypotheses.len(), 1);
+    assert_eq!(hypotheses[0].token_ids, greedy);
+}
+
+#[test]
+fn hypotheses_score_their_tokens() {
+    let model = model();
+    let config = BeamSearchConfig {
+        beam_width: 3,
+        max_new_tokens: 8,
+        num_return_sequences: 3,
+        ..BeamSearchConfig::default()
+    };
+    let hypotheses = beam_search(&model, &PROMPT, &config).unwrap();
+    assert_eq!(hypotheses.len(), 3);
+    assert!(hypotheses.iter().any(|h| ended_by_eos(h, &config)));
+
+    for hypothesis in &hypotheses {
+        let mut sequence = PROMPT.to_vec();
+        let mut log_prob = 0.0;
+        for &token in &hypothesis.token_ids {
+            assert_ne!(token, PAD_TOKEN_ID);
+            log_prob += next_log_probs(&model, &sequence)[token];
+            sequence.push(token);
+        }
+        if ended_by_eos(hypothesis, &config) {
+            log_prob += next_log_probs(&model, &sequence)[EOS_TOKEN_ID];
+        }
+        assert!((hypothesis.log_prob - log_prob).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn n_best_is_ranked_by_length_normalized_score() {
+    let model = model();
+    for length_penalty in [0.0, 1.0, 2.0] {
+        for num_return_sequences in [1, 2, 4, 6] {
+            let config = BeamSearchConfig {
+                beam_width: 4,
+                max_new_tokens: 6,
+                length_penalty,
+                num_return_sequences,
+                ..BeamSearchConfig::default()
+            };
+            let hypotheses = beam_search(&model, &PROMPT, &config).unwrap();
+            assert_eq!(hypotheses.len(), num_return_sequences.min(4));
+            assert!(hypotheses.windows(2).all(|w| w[0].score >= w[1].score));
+            for hypothesis in &hypotheses {
+                let len =
+                    hypothesis.token_ids.len() + usize::from(ended_by_eos(hypothesis, &config));
+                let expected = hypothesis.log_prob / (len as f32).powf(length_penalty);
+                assert!((hypothesis.score - expected).abs() < 1e-5);
+            }
+        }
+    }
+}
+
+#[test]
+fn stronger_length_penalty_prefers_longer_outputs() {
+    let model = model();
+    let best_len = |length_penalty| {
+        let config = BeamSearchConfig {
+            length_penalty,
+            max_new_tokens: 8,
+            ..BeamSearchConfig::default()
+        };
+        beam_search(&model, &PROMPT, &config).unwrap()[0]
+            .token_ids
+            .len()
+    };
+    assert!(best_len(0.0) < best_len(1.0));
+    assert!(best_len(1.0) <= best_len(3.0));
+}
+
+#[test]
+fn prompts_without_room_yield_nothing_or_an_error() {
+    let model = model();
+    let config = BeamSearchConfig::default();
+    assert_eq!(beam_search(&model, &[], &config), Ok(Vec::new()));
+    assert_eq!(beam_search(&model, &[3; 16], &config), Ok(Vec::new()));
+    assert_eq!(
+        beam_search(&model, &[3; 20], &config),
+        Err(SequenceTooLong {
+            seq_len: 20,
+            max_seq_len: 16,
+        })
+    );
+    let hypotheses = beam_search(&model, &[3; 15], &config).unwrap();
+    assert!(hypotheses[0].token_ids.len() <= 1);
+}
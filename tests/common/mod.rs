@@ -0,0 +1,16 @@
+use llm_engine::model::config::ModelConfig;
+
+/// A tiny deterministic model configuration; tests override fields with
+/// `..common::small_config()`
+pub fn small_config() -> ModelConfig {
+    ModelConfig {
+        d_model: 8,
+        n_heads: 2,
+        n_layers: 2,
+        max_seq_len: 8,
+        vocab_size: 11,
+        ff_hidden_size: 12,
+        dropout: 0.0,
+        ..ModelConfig::default()
+    }
+}
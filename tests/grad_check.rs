@@ -0,0 +1,378 @@
+mod common;
+
+use llm_engine::model::common::Param;
+use llm_engine::model::config::{ModelConfig, PositionalEncodingKind};
+use llm_engine::model::layers::{
+    activation::Activation,
+    attention::MultiHeadAttention,
+    embedding::TokenEmbedding,
+    feedforward::FeedForward,
+    linear::Linear,
+    norm::{LayerNorm, NormKind, RMSNorm},
+    positional::PositionEmbedding,
+};
+use llm_engine::model::mask::AttentionMask;
+use llm_engine::model::module::Module;
+use llm_engine::model::tensor::Tensor;
+use llm_engine::model::transformer::SimpleTransformer;
+use llm_engine::utils::grad_check::{
+    GradCheckConfig, ParamAccessor, assert_gradients_match, check_module, check_params,
+};
+
+/// Small deterministic generator so the checks do not depend on `Param::new`
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> f32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+    }
+
+    fn fill(&mut self, param: &mut Param) {
+        param.value.iter_mut().for_each(|v| *v = self.next());
+    }
+
+    fn matrix(&mut self, rows: usize, cols: usize) -> Tensor {
+        Tensor::new(
+            (0..rows * cols).map(|_| self.next()).collect(),
+            &[rows, cols],
+        )
+    }
+}
+
+/// Loss `Σ output ⊙ weights`, whose gradient w.r.t. the output is `weights`
+fn weighted_sum(output: &Tensor, weights: &Tensor) -> f64 {
+    assert_eq!(output.shape(), weights.shape());
+    output
+        .data()
+        .iter()
+        .zip(weights.data())
+        .map(|(a, b)| (a * b) as f64)
+        .sum()
+}
+
+#[test]
+fn linear_gradients() {
+    let mut rng = Lcg(1);
+    let mut layer = Linear::new(6, 4);
+    rng.fill(&mut layer.weight);
+    rng.fill(&mut layer.bias);
+    let inputs = rng.matrix(3, 6);
+    let weights = rng.matrix(3, 4);
+
+    layer.backward(&inputs, &weights);
+
+    let params: [(&str, ParamAccessor<Linear>); 2] =
+        [("weight", |l| &mut l.weight), ("bias", |l| &mut l.bias)];
+    let loss = |l: &Linear| weighted_sum(&l.forward(&inputs), &weights);
+    assert_gradients_match(&check_params(
+        &mut layer,
+        &params,
+        loss,
+        &GradCheckConfig::default(),
+    ));
+}
+
+#[test]
+fn layer_norm_gradients() {
+    let mut rng = Lcg(2);
+    let mut norm = LayerNorm::new(5);
+    rng.fill(&mut norm.gamma);
+    rng.fill(&mut norm.beta);
+    let inputs = rng.matrix(3, 5);
+    let weights = rng.matrix(3, 5);
+
+    norm.backward(&inputs, &weights);
+
+    let params: [(&str, ParamAccessor<LayerNorm>); 2] =
+        [("gamma", |n| &mut n.gamma), ("beta", |n| &mut n.beta)];
+    let loss = |n: &LayerNorm| weighted_sum(&n.forward(&inputs), &weights);
+    assert_gradients_match(&check_params(
+        &mut norm,
+        &params,
+        loss,
+        &GradCheckConfig::default(),
+    ));
+}
+
+#[test]
+fn rms_norm_gradients() {
+    let mut rng = Lcg(8);
+    let mut norm = RMSNorm::new(5);
+    rng.fill(&mut norm.gamma);
+    let inputs = rng.matrix(3, 5);
+    let weights = rng.matrix(3, 5);
+
+    norm.backward(&inputs, &weights);
+
+    let params: [(&str, ParamAccessor<RMSNorm>); 1] = [("gamma", |n| &mut n.gamma)];
+    let loss = |n: &RMSNorm| weighted_sum(&n.forward(&inputs), &weights);
+    assert_gradients_match(&check_params(
+        &mut norm,
+        &params,
+        loss,
+        &GradCheckConfig::default(),
+    ));
+}
+
+#[test]
+fn feed_forward_gradients() {
+    for activation in [Activation::Gelu, Activation::Silu, Activation::SwiGlu] {
+        let mut rng = Lcg(3);
+        let mut ff = FeedForward::new(4, 7, activation);
+        rng.fill(&mut ff.up_proj.weight);
+        rng.fill(&mut ff.down_proj.weight);
+        if let Some(gate) = &mut ff.gate_proj {
+            rng.fill(&mut gate.weight);
+        }
+        let inputs = rng.matrix(2, 4);
+        let weights = rng.matrix(2, 4);
+
+        let (_, cache) = ff.forward_with_cache(&inputs);
+        ff.backward(&cache, &weights);
+
+        let mut params: Vec<(&str, ParamAccessor<FeedForward>)> = vec![
+            ("up_proj.weight", |f| &mut f.up_proj.weight),
+            ("up_proj.bias", |f| &mut f.up_proj.bias),
+            ("down_proj.weight", |f| &mut f.down_proj.weight),
+        ];
+        if activation.is_gated() {
+            params.push(("gate_proj.weight", |f| {
+                &mut f.gate_proj.as_mut().unwrap().weight
+            }));
+        }
+        let loss = |f: &FeedForward| weighted_sum(&f.forward(&inputs), &weights);
+        assert_gradients_match(&check_params(
+            &mut ff,
+            &params,
+            loss,
+            &GradCheckConfig::default(),
+        ));
+    }
+}
+
+#[test]
+fn attention_gradients() {
+    let mut rng = Lcg(4);
+    let mut attn = MultiHeadAttention::new(8, 2);
+    for proj in [
+        &mut attn.query_proj,
+        &mut attn.key_proj,
+        &mut attn.value_proj,
+        &mut attn.out_proj,
+    ] {
+        rng.fill(&mut proj.weight);
+        rng.fill(&mut proj.bias);
+    }
+    let inputs = rng.matrix(4, 8);
+    let weights = rng.matrix(4, 8);
+    let mask = AttentionMask::causal(4);
+
+    let (_, cache) = attn.forward_with_cache(&inputs, Some(&mask));
+    attn.backward(&cache, &weights);
+
+    let params: [(&str, ParamAccessor<MultiHeadAttention>); 5] = [
+        ("query_proj.weight", |a| &mut a.query_proj.weight),
+        ("key_proj.weight", |a| &mut a.key_proj.weight),
+        ("value_proj.weight", |a| &mut a.value_proj.weight),
+        ("value_proj.bias", |a| &mut a.value_proj.bias),
+        ("out_proj.weight", |a| &mut a.out_proj.weight),
+    ];
+    let loss = |a: &MultiHeadAttention| weighted_sum(&a.forward(&inputs, Some(&mask)), &weights);
+    assert_gradients_match(&check_params(
+        &mut attn,
+        &params,
+        loss,
+        &GradCheckConfig::default(),
+    ));
+}
+
+#[test]
+fn embedding_gradients() {
+    let mut rng = Lcg(5);
+    let mut embedding = TokenEmbedding::new(6, 3);
+    rng.fill(&mut embedding.weight);
+    let token_ids = [1, 4, 1, 0];
+    let weights = rng.matrix(token_ids.len(), 3);
+
+    embedding.backward(&token_ids, &weights);
+
+    let params: [(&str, ParamAccessor<TokenEmbedding>); 1] = [("weight", |e| &mut e.weight)];
+    let loss = |e: &TokenEmbedding| weighted_sum(&e.forward(&token_ids), &weights);
+    assert_gradients_match(&check_params(
+        &mut embedding,
+        &params,
+        loss,
+        &GradCheckConfig::default(),
+    ));
+}
+
+fn random_transformer(config: &ModelConfig, rng: &mut Lcg) -> SimpleTransformer {
+    let mut model = SimpleTransformer::from_config(config).unwrap();
+    rng.fill(&mut model.token_embedding.weight);
+    if let Some(PositionEmbedding::Learned(pos)) = &mut model.pos_encoding {
+        rng.fill(&mut pos.weight);
+    }
+    for layer in &mut model.layers {
+        for proj in [
+            &mut layer.attn.query_proj,
+            &mut layer.attn.key_proj,
+            &mut layer.attn.value_proj,
+            &mut layer.attn.out_proj,
+            &mut layer.ff.up_proj,
+            &mut layer.ff.down_proj,
+        ] {
+            rng.fill(&mut proj.weight);
+        }
+        if let Some(gate) = &mut layer.ff.gate_proj {
+            rng.fill(&mut gate.weight);
+        }
+    }
+    if let Some(head) = &mut model.lm_head {
+        rng.fill(&mut head.weight);
+    }
+    // Dropout draws new masks on every forward pass, so finite differences
+    // need the deterministic eval mode
+    model.eval();
+    model
+}
+
+fn check_transformer(config: ModelConfig) {
+    let mut rng = Lcg(6);
+    let mut model = random_transformer(&config, &mut rng);
+
+    let token_ids = [3, 7, 2, 0];
+    let weights = rng.matrix(token_ids.len(), config.vocab_size);
+    let mask = model.default_mask(&token_ids);
+
+    let (_, cache) = model.forward_with_cache(&token_ids, Some(&mask));
+    model.zero_grad();
+    model.backward(&cache, &weights);
+
+    let loss = |m: &SimpleTransformer| {
+        weighted_sum(&m.forward_with_mask(&token_ids, Some(&mask)), &weights)
+    };
+    let config = GradCheckConfig {
+        max_entries: 8,
+        ..GradCheckConfig::default()
+    };
+    assert_gradients_match(&check_module(&mut model, loss, &config));
+}
+
+#[test]
+fn transformer_pre_ln_gradients() {
+    check_transformer(common::small_config());
+}
+
+#[test]
+fn transformer_post_ln_tied_gradients() {
+    check_transformer(ModelConfig {
+        pre_layer_norm: false,
+        weight_sharing: true,
+        ..common::small_config()
+    });
+}
+
+#[test]
+fn transformer_swiglu_gradients() {
+    check_transformer(ModelConfig {
+        activation: Activation::SwiGlu,
+        ..common::small_config()
+    });
+}
+
+#[test]
+fn transformer_learned_positions_gradients() {
+    check_transformer(ModelConfig {
+        learned_positional_encoding: true,
+        ..common::small_config()
+    });
+}
+
+#[test]
+fn transformer_rotary_gradients() {
+    check_transformer(ModelConfig {
+        positional_encoding: PositionalEncodingKind::Rotary {
+            base: 10000.0,
+            fraction: 0.5,
+        },
+        ..common::small_config()
+    });
+}
+
+#[test]
+fn transformer_alibi_gradients() {
+    check_transformer(ModelConfig {
+        positional_encoding: PositionalEncodingKind::Alibi,
+        n_heads: 4,
+        ..common::small_config()
+    });
+}
+
+#[test]
+fn transformer_rms_norm_gradients() {
+    check_transformer(ModelConfig {
+        norm: NormKind::RmsNorm,
+        activation: Activation::SwiGlu,
+        positional_encoding: PositionalEncodingKind::Rotary {
+            base: 10000.0,
+            fraction: 1.0,
+        },
+        ..common::small_config()
+    });
+}
+
+#[test]
+fn transformer_grouped_query_gradients() {
+    check_transformer(ModelConfig {
+        n_heads: 4,
+        n_kv_heads: Some(2),
+        positional_encoding: PositionalEncodingKind::Rotary {
+            base: 10000.0,
+            fraction: 1.0,
+        },
+        ..common::small_config()
+    });
+}
+
+#[test]
+fn transformer_multi_query_gradients() {
+    check_transformer(ModelConfig {
+        n_heads: 4,
+        n_kv_heads: Some(1),
+        positional_encoding: PositionalEncodingKind::Alibi,
+        ..common::small_config()
+    });
+}
+
+#[test]
+fn transformer_batched_gradients() {
+    let config = common::small_config();
+    let mut rng = Lcg(7);
+    let mut model = random_transformer(&config, &mut rng);
+
+    let token_ids = vec![vec![3, 7, 2, 0], vec![5, 1, 0, 0]];
+    let weights = rng
+        .matrix(token_ids.len() * 4, config.vocab_size)
+        .reshape(&[token_ids.len(), 4, config.vocab_size]);
+    let mask = model.default_batch_mask(&token_ids);
+
+    let (_, cache) = model.forward_batch_with_cache(&token_ids, Some(&mask));
+    model.zero_grad();
+    model.backward(&cache, &weights);
+
+    let loss = |m: &SimpleTransformer| {
+        weighted_sum(
+            &m.forward_batch_with_cache(&token_ids, Some(&mask)).0,
+            &weights,
+        )
+    };
+    let config = GradCheckConfig {
+        max_entries: 8,
+        ..GradCheckConfig::default()
+    };
+    assert_gradients_match(&check_module(&mut model, loss, &config));
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use llm_engine::inference::{GenerationConfig, InferenceEngine};
+use llm_engine::model::config::ModelConfig;
+use llm_engine::model::layers::positional::SequenceTooLong;
+use llm_engine::model::transformer::SimpleTransformer;
+use llm_engine::sampler::Sampler;
+use llm_engine::tokenizer::{PAD_TOKEN_ID, Tokenizer};
+
+#[test]
+fn padding_is_never_generated() {
+    let mut model = SimpleTransformer::from_config(&ModelConfig {
+        max_seq_len: 16,
+        ..common::small_config()
+    })
+    .unwrap();
+    let head = model.lm_head.as_mut().unwrap();
+    head.bias.value[PAD_TOKEN_ID] = 100.0;
+    let engine = InferenceEngine::new(model, Tokenizer::new());
+    let config = GenerationConfig {
+        max_new_tokens: 8,
+        eos_token_id: None,
+    };
+    for mut sampler in [Sampler::greedy(), Sampler::new(Some(3))] {
+        let generated = engine
+            .generate_ids(&[3, 4, 5], &config, &mut sampler)
+            .unwrap();
+        assert_eq!(generated.len(), 8);
+        assert!(!generated.contains(&PAD_TOKEN_ID));
+    }
+}
+
+#[test]
+fn over_length_prompts_are_errors() {
+    let engine = InferenceEngine::new(
+        SimpleTransformer::from_config(&common::small_config()).unwrap(),
+        Tokenizer::new(),
+    );
+    let config = GenerationConfig::default();
+    let generated = engine.generate_ids(&[3; 9], &config, &mut Sampler::greedy());
+    assert_eq!(
+        generated.err(),
+        Some(SequenceTooLong {
+            seq_len: 9,
+            max_seq_len: 8,
+        })
+    );
+    // A prompt filling the context is valid but leaves no room to generate
+    let generated = engine.generate_ids(&[3; 8], &config, &mut Sampler::greedy());
+    assert_eq!(generated, Ok(Vec::new()));
+}
+
+#[test]
+fn engine_runs_the_model_in_eval_mode() {
+    let model = SimpleTransformer::from_config(&ModelConfig {
+        dropout: 0.5,
+        seed: Some(5),
+        max_seq_len: 16,
+        ..common::small_config()
+    })
+    .unwrap();
+    let engine = InferenceEngine::new(model, Tokenizer::new());
+    let config = GenerationConfig {
+        max_new_tokens: 12,
+        eos_token_id: None,
+    };
+    // With dropout active, repeated greedy runs would see different masks
+    let generate = || {
+        engine
+            .generate_ids(&[3, 4, 5], &config, &mut Sampler::greedy())
+            .unwrap()
+    };
+    let first = generate();
+    for _ in 0..3 {
+        assert_eq!(generate(), first);
+    }
+}
@@ -0,0 +1,71 @@
+use llm_engine::model::common::Param;
+use llm_engine::model::tensor::Tensor;
+use llm_engine::training::loss::cross_entropy_loss;
+use llm_engine::utils::grad_check::{
+    GradCheckConfig, ParamAccessor, assert_gradients_match, check_params,
+};
+
+const VOCAB_SIZE: usize = 7;
+const IGNORE: usize = 0;
+
+fn logits(rows: usize, seed: usize) -> Tensor {
+    let data = (0..rows * VOCAB_SIZE)
+        .map(|i| ((i * 37 + seed * 11) % 23) as f32 / 5.0 - 2.0)
+        .collect();
+    Tensor::new(data, &[rows, VOCAB_SIZE])
+}
+
+#[test]
+fn cross_entropy_gradients() {
+    let targets = [3, IGNORE, 6, 1];
+    for label_smoothing in [0.0, 0.1] {
+        let loss = |logits: &Param| {
+            let logits = Tensor::new(logits.value.clone(), &[targets.len(), VOCAB_SIZE]);
+            cross_entropy_loss(&logits, &targets, Some(IGNORE), label_smoothing).0 as f64
+        };
+        let mut logits = Param::from_values(logits(targets.len(), 1).into_data());
+        let shaped = Tensor::new(logits.value.clone(), &[targets.len(), VOCAB_SIZE]);
+        let (_, grad) = cross_entropy_loss(&shaped, &targets, Some(IGNORE), label_smoothing);
+        logits.grad = grad.into_data();
+
+        let params: [(&str, ParamAccessor<Param>); 1] = [("logits", |p| p)];
+        assert_gradients_match(&check_params(
+            &mut logits,
+            &params,
+            loss,
+            &GradCheckConfig::default(),
+        ));
+    }
+}
+
+#[test]
+fn ignored_positions_have_no_loss_or_gradient() {
+    let targets = [3, IGNORE, 6, IGNORE];
+    let all = logits(4, 2);
+    let (loss, grad) = cross_entropy_loss(&all, &targets, Some(IGNORE), 0.1);
+
+    for row in [1, 3] {
+        assert!(grad.row(row).iter().all(|&g| g == 0.0));
+    }
+
+    // Same loss as if the ignored rows were never there, whatever they hold
+    let kept = Tensor::from_rows(&[all.row(0).to_vec(), all.row(2).to_vec()]);
+    let (kept_loss, kept_grad) = cross_entropy_loss(&kept, &[3, 6], Some(IGNORE), 0.1);
+    assert!((loss - kept_loss).abs() < 1e-6);
+    assert_eq!(grad.row(0), kept_grad.row(0));
+    assert_eq!(grad.row(2), kept_grad.row(1));
+
+    let mut changed = all.clone();
+    changed.data_mut()[VOCAB_SIZE..2 * VOCAB_SIZE].fill(9.0);
+    assert_eq!(
+        cross_entropy_loss(&changed, &targets, Some(IGNORE), 0.1).0,
+        loss
+    );
+}
+
+#[test]
+fn fully_ignored_batch_has_zero_loss() {
+    let (loss, grad) = cross_entropy_loss(&logits(2, 3), &[IGNORE, IGNORE], Some(IGNORE), 0.0);
+    assert_eq!(loss, 0.0);
+    assert!(grad.data().iter().all(|&g| g == 0.0));
+}
@@ -0,0 +1,89 @@
+use llm_engine::model::mask::AttentionMask;
+use llm_engine::model::tensor::Tensor;
+
+const NEG: f32 = f32::NEG_INFINITY;
+
+fn assert_bias(mask: &AttentionMask, shape: &[usize], expected: &[f32]) {
+    assert_eq!(mask.bias, Tensor::new(expected.to_vec(), shape));
+}
+
+#[test]
+fn causal_blocks_later_positions() {
+    assert_bias(
+        &AttentionMask::causal(3),
+        &[3, 3],
+        &[
+            0.0, NEG, NEG, //
+            0.0, 0.0, NEG, //
+            0.0, 0.0, 0.0,
+        ],
+    );
+    // The last two of four positions, as when decoding with a KV cache
+    assert_bias(
+        &AttentionMask::causal_with_offset(2, 4),
+        &[2, 4],
+        &[
+            0.0, 0.0, 0.0, NEG, //
+            0.0, 0.0, 0.0, 0.0,
+        ],
+    );
+}
+
+#[test]
+fn key_padding_blocks_padded_keys_for_every_query() {
+    let mask = AttentionMask::key_padding(&[5, 0, 7, 0], 0);
+    assert_eq!(mask.batch_size(), None);
+    assert_bias(&mask, &[4, 4], &[0.0, NEG, 0.0, NEG].repeat(4));
+
+    let mask = AttentionMask::from_padding(&[vec![true, true, false], vec![true, false, false]]);
+    assert_eq!(mask.batch_size(), Some(2));
+    assert_bias(
+        &mask,
+        &[2, 3, 3],
+        &[[0.0, 0.0, NEG].repeat(3), [0.0, NEG, NEG].repeat(3)].concat(),
+    );
+}
+
+#[test]
+fn bool_and_additive_masks_keep_their_entries() {
+    let mask = AttentionMask::from_bool(&[vec![true, false], vec![false, true]]);
+    assert_bias(&mask, &[2, 2], &[0.0, NEG, NEG, 0.0]);
+
+    let mask = AttentionMask::from_additive(vec![vec![0.5, -1.0, NEG], vec![0.0, 2.0, 0.0]]);
+    assert_eq!((mask.query_len(), mask.key_len()), (2, 3));
+    assert_bias(&mask, &[2, 3], &[0.5, -1.0, NEG, 0.0, 2.0, 0.0]);
+}
+
+#[test]
+fn stack_adds_a_batch_axis() {
+    let mask = AttentionMask::stack(&[
+        AttentionMask::causal(2),
+        AttentionMask::from_bool(&[vec![false, true], vec![true, true]]),
+    ]);
+    assert_eq!(mask.batch_size(), Some(2));
+    assert_bias(&mask, &[2, 2, 2], &[0.0, NEG, 0.0, 0.0, NEG, 0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn combine_blocks_keys_blocked_by_either_mask() {
+    let causal = AttentionMask::causal(3);
+    let padding = AttentionMask::key_padding(&[4, 0, 6], 0);
+    assert_bias(
+        &causal.combine(&padding),
+        &[3, 3],
+        &[
+            0.0, NEG, NEG, //
+            0.0, NEG, NEG, //
+            0.0, NEG, 0.0,
+        ],
+    );
+
+    // Biases add up, and an unbatched mask is broadcast over the batch
+    let bias = AttentionMask::from_additive(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+    let batched = AttentionMask::from_padding(&[vec![true, true], vec![true, false]]);
+    assert_bias(
+        &batched.combine(&bias),
+        &[2, 2, 2],
+        &[1.0, 2.0, 3.0, 4.0, 1.0, NEG, 3.0, NEG],
+    );
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use llm_engine::model::common::Param;
+use llm_engine::model::config::ModelConfig;
+use llm_engine::model::module::Module;
+use llm_engine::model::transformer::SimpleTransformer;
+use llm_engine::training::optimizer::{Adam, Optimizer, SGD};
+
+fn model_with_grads() -> SimpleTransformer {
+    let mut model = SimpleTransformer::from_config(&common::small_config()).unwrap();
+    for (i, (_, param)) in model.parameters_mut().into_iter().enumerate() {
+        for (j, g) in param.grad.iter_mut().enumerate() {
+            *g = ((i * 7 + j * 3) % 11) as f32 / 11.0 - 0.5;
+        }
+    }
+    model
+}
+
+/// Two `step`s must match a `step` followed by a `step_groups`, which visits
+/// the same parameters in a different order
+fn check_state_follows_names(mut make_optimizer: impl FnMut() -> Box<dyn Optimizer>) {
+    let mut reference = model_with_grads();
+    let mut optimizer = make_optimizer();
+    optimizer.step(&mut reference.parameters_mut());
+    optimizer.step(&mut reference.parameters_mut());
+
+    let mut mixed = model_with_grads();
+    let mut optimizer = make_optimizer();
+    optimizer.step(&mut mixed.parameters_mut());
+    optimizer.step_groups(&mut mixed.param_groups(1.0));
+
+    assert_eq!(mixed.state_dict(), reference.state_dict());
+}
+
+#[test]
+fn sgd_momentum_is_keyed_by_name() {
+    check_state_follows_names(|| Box::new(SGD::new(0.1).with_momentum(0.9, true)));
+}
+
+#[test]
+fn adam_moments_are_keyed_by_name() {
+    check_state_follows_names(|| Box::new(Adam::new(0.01)));
+}
+
+/// A parameter `w` with values `[1, -2]` and gradient `grad`
+fn param(grad: [f32; 2]) -> Param {
+    let mut param = Param::from_values(vec![1.0, -2.0]);
+    param.grad = grad.to_vec();
+    param
+}
+
+fn step(optimizer: &mut dyn Optimizer, param: &mut Param) {
+    optimizer.step(&mut [("w".to_string(), param)]);
+}
+
+fn assert_values(param: &Param, expected: [f32; 2]) {
+    for (actual, expected) in param.value.iter().zip(expected) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} vs {expected}");
+    }
+}
+
+#[test]
+fn sgd_momentum_matches_hand_computed_steps() {
+    // buf = 0.9 · buf + g; w -= 0.1 · buf
+    let mut optimizer = SGD::new(0.1).with_momentum(0.9, false);
+    let mut w = param([0.5, 0.25]);
+    step(&mut optimizer, &mut w);
+    assert_values(&w, [0.95, -2.025]);
+    step(&mut optimizer, &mut w);
+    assert_values(&w, [0.855, -2.0725]);
+}
+
+#[test]
+fn nesterov_momentum_matches_hand_computed_steps() {
+    // buf = 0.9 · buf + g; w -= 0.1 · (g + 0.9 · buf)
+    let mut optimizer = SGD::new(0.1).with_momentum(0.9, true);
+    let mut w = param([0.5, 0.25]);
+    step(&mut optimizer, &mut w);
+    assert_values(&w, [0.905, -2.0475]);
+    step(&mut optimizer, &mut w);
+    assert_values(&w, [0.7695, -2.11525]);
+}
+
+#[test]
+fn adam_corrects_the_moment_bias() {
+    // With constant gradients the bias-corrected moments are m̂ = g and
+    // v̂ = g², so every step moves each weight by lr · sign(g); without the
+    // correction the first step would be lr · 0.1 / √0.001 ≈ 3.16 · lr
+    let mut optimizer = Adam::new(0.01);
+    let mut w = param([0.5, -0.25]);
+    step(&mut optimizer, &mut w);
+    assert_values(&w, [0.99, -1.99]);
+    step(&mut optimizer, &mut w);
+    assert_values(&w, [0.98, -1.98]);
+    assert_eq!(optimizer.steps(), 2);
+}
+
+#[test]
+fn adamw_decays_weights_instead_of_gradients() {
+    // AdamW: w -= lr · wd · w, then the Adam step on the raw gradient
+    let mut optimizer = Adam::adamw(0.1, 0.1);
+    let mut w = param([0.5, 0.1]);
+    step(&mut optimizer, &mut w);
+    assert_values(&w, [0.99 - 0.1, -1.98 - 0.1]);
+
+    // L2: the Adam step on g + wd · w, which flips the sign of the second gradient
+    let mut optimizer = Adam::new(0.1);
+    optimizer.weight_decay = 0.1;
+    let mut w = param([0.5, 0.1]);
+    step(&mut optimizer, &mut w);
+    assert_values(&w, [0.9, -1.9]);
+}
+
+#[test]
+fn param_groups_skip_decay_and_scale_by_depth() {
+    let mut model = SimpleTransformer::from_config(&ModelConfig {
+        learned_positional_encoding: true,
+        ..common::small_config()
+    })
+    .unwrap();
+    let names: BTreeSet<String> = model
+        .parameters()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+
+    let mut grouped = BTreeMap::new();
+    for group in model.param_groups(0.5) {
+        for (name, _) in group.params {
+            let previous = grouped.insert(name, (group.lr_scale, group.weight_decay));
+            assert!(previous.is_none(), "a parameter is in two groups");
+        }
+    }
+    assert!(grouped.keys().eq(&names));
+
+    // Embeddings sit below layer 0 and the final norm and head above the last
+    // layer, so with n = 2 layers: 0.5³, then 0.5^(n - i) for layer i, then 1
+    for (name, lr_scale, weight_decay) in [
+        ("token_embedding.weight", 0.125, Some(0.0)),
+        ("pos_encoding.weight", 0.125, Some(0.0)),
+        ("layers.0.attn.query_proj.weight", 0.25, None),
+        ("layers.0.attn.query_proj.bias", 0.25, Some(0.0)),
+        ("layers.0.attn_norm.gamma", 0.25, Some(0.0)),
+        ("layers.0.ff.up_proj.weight", 0.25, None),
+        ("layers.1.attn.out_proj.weight", 0.5, None),
+        ("layers.1.ff.down_proj.bias", 0.5, Some(0.0)),
+        ("layers.1.ff_norm.beta", 0.5, Some(0.0)),
+        ("final_norm.gamma", 1.0, Some(0.0)),
+        ("lm_head.weight", 1.0, None),
+        ("lm_head.bias", 1.0, Some(0.0)),
+    ] {
+        assert_eq!(grouped[name], (lr_scale, weight_decay), "{name}");
+    }
+    for (name, &(lr_scale, _)) in &grouped {
+        if let Some(rest) = name.strip_prefix("layers.") {
+            let i: i32 = rest.split('.').next().unwrap().parse().unwrap();
+            assert_eq!(lr_scale, 0.5f32.powi(2 - i), "{name}");
+        }
+    }
+}
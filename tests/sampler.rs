@@ -0,0 +1,91 @@
+use std::collections::BTreeSet;
+
+use llm_engine::sampler::{Sampler, SamplingConfig, SamplingError};
+
+/// Logits whose softmax is `[0.4, 0.3, 0.15, 0.1, 0.05]`
+fn logits() -> Vec<f32> {
+    [0.4f32, 0.3, 0.15, 0.1, 0.05]
+        .iter()
+        .map(|p| p.ln())
+        .collect()
+}
+
+/// Token IDs drawn at least once in many draws
+fn support(mut sampler: Sampler) -> BTreeSet<usize> {
+    let logits = logits();
+    (0..2000).map(|_| sampler.sample(&logits)).collect()
+}
+
+#[test]
+fn same_seed_gives_same_tokens() {
+    let logits = logits();
+    let draw = |seed| {
+        let mut sampler = Sampler::new(Some(seed)).temperature(1.5);
+        (0..64).map(|_| sampler.sample(&logits)).collect::<Vec<_>>()
+    };
+    assert_eq!(draw(7), draw(7));
+    assert_ne!(draw(7), draw(8));
+}
+
+#[test]
+fn unfiltered_sampling_reaches_every_token() {
+    assert_eq!(
+        support(Sampler::new(Some(0))),
+        BTreeSet::from([0, 1, 2, 3, 4])
+    );
+}
+
+#[test]
+fn greedy_picks_the_most_likely_token() {
+    assert_eq!(support(Sampler::greedy()), BTreeSet::from([0]));
+}
+
+#[test]
+fn filters_keep_the_expected_candidates() {
+    let sampler = || Sampler::new(Some(1));
+    assert_eq!(support(sampler().top_k(2)), BTreeSet::from([0, 1]));
+    // Cumulative mass 0.4, 0.7, 0.85: three tokens reach 0.8
+    assert_eq!(support(sampler().top_p(0.8)), BTreeSet::from([0, 1, 2]));
+    // Tokens with at least 0.2 · 0.4 = 0.08 probability
+    assert_eq!(support(sampler().min_p(0.2)), BTreeSet::from([0, 1, 2, 3]));
+    // Entropy ≈ 1.39 nats; token 1 (surprisal ≈ 1.20) is the most typical and
+    // already holds 0.3 of the mass
+    assert_eq!(support(sampler().typical(0.25)), BTreeSet::from([1]));
+    // Token 0 (surprisal ≈ 0.92) is next most typical
+    assert_eq!(support(sampler().typical(0.6)), BTreeSet::from([0, 1]));
+    // Steps chain in order: top-k keeps 0..3, then min-p drops token 2
+    assert_eq!(
+        support(sampler().top_k(3).min_p(0.5)),
+        BTreeSet::from([0, 1])
+    );
+}
+
+#[test]
+fn greedy_config_rejects_sampling_fields() {
+    assert!(SamplingConfig::default().build().is_ok());
+    let config = SamplingConfig {
+        top_k: Some(5),
+        top_p: Some(0.9),
+        seed: Some(7),
+        ..SamplingConfig::default()
+    };
+    assert_eq!(
+        config.build().err(),
+        Some(SamplingError::UnusedWhenGreedy("top_k"))
+    );
+    let config = SamplingConfig {
+        seed: Some(7),
+        ..SamplingConfig::default()
+    };
+    assert_eq!(
+        config.build().err(),
+        Some(SamplingError::UnusedWhenGreedy("seed"))
+    );
+    let config = SamplingConfig {
+        temperature: 0.8,
+        top_k: Some(5),
+        seed: Some(7),
+        ..SamplingConfig::default()
+    };
+    assert!(config.build().is_ok());
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use llm_engine::model::config::ModelConfig;
+use llm_engine::model::module::Module;
+use llm_engine::model::transformer::SimpleTransformer;
+use llm_engine::serialization::{load_model, save_model};
+
+fn saved_model(config: &ModelConfig) -> (SimpleTransformer, Vec<u8>) {
+    let model = SimpleTransformer::from_config(config).unwrap();
+    let mut bytes = Vec::new();
+    save_model(&model, &mut bytes).unwrap();
+    (model, bytes)
+}
+
+#[test]
+fn round_trip_restores_parameters() {
+    let config = common::small_config();
+    let (model, bytes) = saved_model(&config);
+    let loaded = load_model(
+        &mut bytes.as_slice(),
+        &ModelConfig {
+            seed: Some(7),
+            ..config
+        },
+    )
+    .unwrap();
+    assert_eq!(loaded.state_dict(), model.state_dict());
+}
+
+#[test]
+fn corrupt_lengths_are_errors() {
+    let config = common::small_config();
+    let (_, bytes) = saved_model(&config);
+    let name_len = u64::from_le_bytes(bytes[8..16].try_into().unwrap()) as usize;
+    let values_len_at = 16 + name_len;
+    for offset in [8, values_len_at] {
+        let mut corrupt = bytes.clone();
+        corrupt[offset..offset + 8].copy_from_slice(&(i64::MAX as u64).to_le_bytes());
+        assert!(load_model(&mut corrupt.as_slice(), &config).is_err());
+    }
+}
+
+#[test]
+fn truncated_file_is_an_error() {
+    let config = common::small_config();
+    let (_, bytes) = saved_model(&config);
+    assert!(load_model(&mut &bytes[..bytes.len() - 1], &config).is_err());
+}
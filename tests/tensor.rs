@@ -0,0 +1,132 @@
+use llm_engine::model::tensor::Tensor;
+
+/// `[shape]` tensor holding `0, 1, 2, ..`
+fn arange(shape: &[usize]) -> Tensor {
+    let len = shape.iter().product::<usize>();
+    Tensor::new((0..len).map(|i| i as f32).collect(), shape)
+}
+
+/// Naive `[m, k] · [k, n]` product over `get`, for comparison
+fn reference_matmul(a: &Tensor, b: &Tensor) -> Tensor {
+    let (m, k, n) = (a.dim(0), a.dim(1), b.dim(1));
+    let mut out = Tensor::zeros(&[m, n]);
+    for i in 0..m {
+        for j in 0..n {
+            *out.get_mut(&[i, j]) = (0..k).map(|p| a.get(&[i, p]) * b.get(&[p, j])).sum();
+        }
+    }
+    out
+}
+
+#[test]
+fn element_wise_ops_broadcast_trailing_axes() {
+    let a = arange(&[2, 3]);
+    let row = Tensor::new(vec![10.0, 20.0, 30.0], &[3]);
+    let sum = &a + &row;
+    assert_eq!(sum.shape(), &[2, 3]);
+    assert_eq!(sum.data(), &[10.0, 21.0, 32.0, 13.0, 24.0, 35.0]);
+
+    // [2, 1, 3] against [4, 1] expands both to [2, 4, 3]
+    let x = arange(&[2, 1, 3]);
+    let y = arange(&[4, 1]);
+    let product = &x * &y;
+    assert_eq!(product.shape(), &[2, 4, 3]);
+    for i in 0..2 {
+        for j in 0..4 {
+            for k in 0..3 {
+                assert_eq!(product.get(&[i, j, k]), x.get(&[i, 0, k]) * y.get(&[j, 0]));
+            }
+        }
+    }
+
+    let mut acc = arange(&[2, 3]);
+    acc.add_assign(&row.view());
+    assert_eq!(acc, sum);
+}
+
+#[test]
+#[should_panic(expected = "cannot be broadcast")]
+fn incompatible_shapes_do_not_broadcast() {
+    let _ = &arange(&[2, 3]) + &arange(&[2]);
+}
+
+#[test]
+fn views_index_through_strides() {
+    let a = arange(&[2, 3, 4]);
+    let t = a.view().transpose(0, 2);
+    assert_eq!(t.shape(), &[4, 3, 2]);
+    assert!(!t.is_contiguous());
+    assert_eq!(t.get(&[3, 1, 0]), a.get(&[0, 1, 3]));
+
+    let narrowed = a.view().narrow(2, 1, 2).narrow(0, 1, 1);
+    assert_eq!(narrowed.shape(), &[1, 3, 2]);
+    assert_eq!(
+        narrowed.to_tensor().data(),
+        &[13.0, 14.0, 17.0, 18.0, 21.0, 22.0]
+    );
+
+    let selected = a.view().select(1, 2);
+    assert_eq!(selected.shape(), &[2, 4]);
+    assert_eq!(selected.get(&[1, 3]), a.get(&[1, 2, 3]));
+}
+
+#[test]
+fn matmul_reads_strided_operands() {
+    let a = arange(&[3, 4]);
+    let b = arange(&[5, 4]).map(|x| x * 0.5 - 3.0);
+
+    // a · bᵀ through a transposed view
+    let expected = reference_matmul(&a, &b.transpose(0, 1));
+    assert_eq!(a.view().matmul(&b.view().t()), expected);
+
+    // Narrowed operands in both positions
+    let a_cols = a.view().narrow(1, 1, 2);
+    let b_rows = b.view().narrow(0, 2, 2);
+    let expected = reference_matmul(&a_cols.to_tensor(), &b_rows.to_tensor());
+    assert_eq!(a_cols.matmul(&b_rows), expected);
+}
+
+#[test]
+fn matmul_broadcasts_batch_axes() {
+    let x = arange(&[2, 3, 4]);
+    let w = arange(&[4, 5]).map(|v| 1.0 - v * 0.1);
+    let out = x.matmul(&w);
+    assert_eq!(out.shape(), &[2, 3, 5]);
+    for batch in 0..2 {
+        let expected = reference_matmul(&x.narrow(0, batch, 1).reshape(&[3, 4]), &w);
+        assert_eq!(out.narrow(0, batch, 1).reshape(&[3, 5]), expected);
+    }
+}
+
+#[test]
+fn matmul_propagates_nan_and_infinity() {
+    // 0 · NaN and 0 · ∞ are NaN, so zero entries must not be skipped
+    let a = Tensor::new(vec![0.0, 1.0], &[1, 2]);
+    let b = Tensor::new(vec![f32::NAN, f32::INFINITY, 2.0, 3.0], &[2, 2]);
+    let out = a.matmul(&b);
+    assert!(out.data().iter().all(|x| x.is_nan()));
+}
+
+#[test]
+fn sum_axis_removes_the_axis() {
+    let a = arange(&[2, 3, 4]);
+    for axis in 0..3 {
+        let summed = a.sum_axis(axis);
+        let mut shape = a.shape().to_vec();
+        shape.remove(axis);
+        assert_eq!(summed.shape(), shape.as_slice());
+        for i in 0..shape[0] {
+            for j in 0..shape[1] {
+                let expected: f32 = (0..a.dim(axis))
+                    .map(|s| {
+                        let mut index = vec![i, j];
+                        index.insert(axis, s);
+                        a.get(&index)
+                    })
+                    .sum();
+                assert_eq!(summed.get(&[i, j]), expected);
+            }
+        }
+    }
+    assert_eq!(a.sum(), (0..24).sum::<usize>() as f32);
+}
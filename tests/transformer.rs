@@ -0,0 +1,297 @@
+mod common;
+
+use llm_engine::model::config::{ConfigError, ModelConfig, PositionalEncodingKind};
+use llm_engine::model::init::Init;
+use llm_engine::model::layers::alibi::alibi_slopes;
+use llm_engine::model::layers::dropout::Dropout;
+use llm_engine::model::layers::positional::SequenceTooLong;
+use llm_engine::model::module::Module;
+use llm_engine::model::tensor::Tensor;
+use llm_engine::model::transformer::SimpleTransformer;
+use llm_engine::tokenizer::PAD_TOKEN_ID;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+#[test]
+fn seeded_models_are_identical() {
+    for init in [Init::default(), Init::XavierUniform, Init::KaimingNormal] {
+        let config = ModelConfig {
+            init,
+            learned_positional_encoding: true,
+            seed: Some(123),
+            ..common::small_config()
+        };
+        let a = SimpleTransformer::from_config(&config).unwrap();
+        let b = SimpleTransformer::from_config(&config).unwrap();
+        assert_eq!(a.state_dict(), b.state_dict());
+
+        let other = SimpleTransformer::from_config(&ModelConfig {
+            seed: Some(124),
+            ..config
+        })
+        .unwrap();
+        assert_ne!(a.state_dict(), other.state_dict());
+    }
+}
+
+fn assert_close(actual: &Tensor, expected: &Tensor) {
+    assert_eq!(actual.shape(), expected.shape());
+    for (a, e) in actual.data().iter().zip(expected.data()) {
+        assert!((a - e).abs() < 1e-4, "{a} vs {e}");
+    }
+}
+
+/// Logits of `tokens[prompt_len..]` obtained one token at a time from the KV cache
+fn incremental_logits(model: &SimpleTransformer, tokens: &[usize], prompt_len: usize) -> Tensor {
+    let mut kv_cache = model.new_kv_cache();
+    let prompt_logits = model.forward_incremental(&tokens[..prompt_len], &mut kv_cache);
+    let mut rows = prompt_logits.to_rows();
+    for &token in &tokens[prompt_len..] {
+        rows.extend(model.forward_incremental(&[token], &mut kv_cache).to_rows());
+    }
+    assert_eq!(kv_cache.len(), tokens.len());
+    Tensor::from_rows(&rows)
+}
+
+#[test]
+fn incremental_decoding_matches_full_forward() {
+    let tokens = [3, 7, 1, 9, 4, 4, 10, 2];
+    for positional_encoding in [
+        PositionalEncodingKind::Absolute,
+        PositionalEncodingKind::Rotary {
+            base: 10000.0,
+            fraction: 1.0,
+        },
+        PositionalEncodingKind::Alibi,
+    ] {
+        for n_kv_heads in [None, Some(2), Some(1)] {
+            for pre_layer_norm in [true, false] {
+                let model = SimpleTransformer::from_config(&ModelConfig {
+                    n_heads: 4,
+                    n_kv_heads,
+                    positional_encoding,
+                    pre_layer_norm,
+                    init: Init::Normal { std: 0.3 },
+                    ..common::small_config()
+                })
+                .unwrap();
+                assert_close(
+                    &incremental_logits(&model, &tokens, 3),
+                    &model.forward(&tokens),
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn truncated_and_reset_caches_match_full_forward() {
+    let model = SimpleTransformer::from_config(&ModelConfig {
+        init: Init::Normal { std: 0.3 },
+        ..common::small_config()
+    })
+    .unwrap();
+    let mut kv_cache = model.new_kv_cache();
+    model.forward_incremental(&[3, 7, 1, 9, 4], &mut kv_cache);
+
+    // Roll back to the first two tokens and continue differently
+    kv_cache.truncate(2);
+    assert_eq!(kv_cache.len(), 2);
+    let logits = model.forward_incremental(&[5, 6], &mut kv_cache);
+    let full = model.forward(&[3, 7, 5, 6]);
+    assert_close(&logits, &full.narrow(0, 2, 2));
+
+    kv_cache.reset();
+    assert!(kv_cache.is_empty());
+    let logits = model.forward_incremental(&[8, 2, 6], &mut kv_cache);
+    assert_close(&logits, &model.forward(&[8, 2, 6]));
+}
+
+#[test]
+fn padded_batch_matches_unpadded_forward() {
+    let sequences = [vec![3, 7, 1, 9, 4], vec![5, 2], vec![8, 6, 10]];
+    let padded: Vec<Vec<usize>> = sequences
+        .iter()
+        .map(|seq| {
+            let mut padded = seq.clone();
+            padded.resize(5, PAD_TOKEN_ID);
+            padded
+        })
+        .collect();
+    let keep: Vec<Vec<bool>> = sequences
+        .iter()
+        .map(|seq| (0..5).map(|i| i < seq.len()).collect())
+        .collect();
+
+    for causal in [true, false] {
+        let model = SimpleTransformer::from_config(&ModelConfig {
+            causal,
+            init: Init::Normal { std: 0.3 },
+            ..common::small_config()
+        })
+        .unwrap();
+        for logits in [
+            model.forward_batch(&padded, None),
+            model.forward_batch(&padded, Some(&keep)),
+        ] {
+            assert_eq!(logits.shape(), &[3, 5, 11]);
+            for (b, seq) in sequences.iter().enumerate() {
+                let rows = logits
+                    .narrow(0, b, 1)
+                    .narrow(1, 0, seq.len())
+                    .reshape(&[seq.len(), 11]);
+                assert_close(&rows, &model.forward(seq));
+            }
+        }
+    }
+}
+
+#[test]
+fn pooled_output_ignores_padding() {
+    for causal in [true, false] {
+        let model = SimpleTransformer::from_config(&ModelConfig {
+            causal,
+            init: Init::Normal { std: 0.3 },
+            ..common::small_config()
+        })
+        .unwrap();
+        let pooled = model.pooled_output(&[3, 7, 1]);
+        let padded = model.pooled_output(&[3, 7, 1, PAD_TOKEN_ID, PAD_TOKEN_ID]);
+        assert_eq!(pooled.len(), 8);
+        for (p, e) in padded.iter().zip(&pooled) {
+            assert!((p - e).abs() < 1e-5, "{p} vs {e}");
+        }
+        assert_eq!(model.pooled_output(&[PAD_TOKEN_ID; 3]), vec![0.0; 8]);
+    }
+}
+
+#[test]
+fn next_token_logits_match_the_last_forward_row() {
+    let tokens = [3, 7, 1, 9, 4];
+    for causal in [true, false] {
+        let model = SimpleTransformer::from_config(&ModelConfig {
+            causal,
+            init: Init::Normal { std: 0.3 },
+            ..common::small_config()
+        })
+        .unwrap();
+        let mut kv_cache = model.new_kv_cache();
+        for len in 2..=tokens.len() {
+            let mut logits = model.next_token_logits(&tokens[..len], &mut kv_cache);
+            assert_eq!(logits[PAD_TOKEN_ID], f32::NEG_INFINITY);
+            let expected = model.forward(&tokens[..len]).narrow(0, len - 1, 1);
+            logits[PAD_TOKEN_ID] = expected.data()[PAD_TOKEN_ID];
+            assert_close(&Tensor::from_rows(&[logits]), &expected);
+        }
+        assert_eq!(kv_cache.len(), if causal { tokens.len() } else { 0 });
+    }
+}
+
+#[test]
+fn dropout_drops_in_train_mode_only() {
+    let mut dropout = Dropout::new(0.5, &mut StdRng::seed_from_u64(1));
+    let input = Tensor::new(vec![1.0; 64], &[8, 8]);
+    let (first, mask) = dropout.forward(input.clone());
+    assert!(mask.is_some());
+    assert!(first.data().iter().all(|&x| x == 0.0 || x == 2.0));
+    assert!(first.data().contains(&0.0));
+    assert_ne!(dropout.forward(input.clone()).0, first);
+
+    dropout.set_training(false);
+    let (output, mask) = dropout.forward(input.clone());
+    assert!(mask.is_none());
+    assert_eq!(output, input);
+}
+
+#[test]
+fn dropout_is_seeded_and_off_in_eval_mode() {
+    let config = ModelConfig {
+        dropout: 0.5,
+        seed: Some(5),
+        ..common::small_config()
+    };
+    let tokens = [3, 7, 1, 9, 4];
+    let mut a = SimpleTransformer::from_config(&config).unwrap();
+    let b = SimpleTransformer::from_config(&config).unwrap();
+
+    // Same seed, same masks: both models drop the same values on every call
+    let first = a.forward(&tokens);
+    assert_eq!(b.forward(&tokens), first);
+    let second = a.forward(&tokens);
+    assert_eq!(b.forward(&tokens), second);
+    assert_ne!(first, second);
+
+    a.eval();
+    let logits = a.forward(&tokens);
+    assert_eq!(a.forward(&tokens), logits);
+    assert_ne!(logits, first);
+}
+
+#[test]
+fn over_length_input_is_an_error() {
+    let model = SimpleTransformer::from_config(&common::small_config()).unwrap();
+    let too_long = SequenceTooLong {
+        seq_len: 9,
+        max_seq_len: 8,
+    };
+    assert_eq!(model.try_forward(&[3; 9]).err(), Some(too_long));
+    assert_eq!(
+        model
+            .try_forward_batch(&[vec![3; 9], vec![4; 9]], None)
+            .err(),
+        Some(too_long)
+    );
+    assert_eq!(model.try_forward(&[3; 8]).unwrap().shape(), &[8, 11]);
+
+    let mut kv_cache = model.new_kv_cache();
+    model
+        .try_forward_incremental(&[3; 6], &mut kv_cache)
+        .unwrap();
+    let err = model.try_forward_incremental(&[4; 3], &mut kv_cache);
+    assert_eq!(err.err(), Some(too_long));
+    assert_eq!(kv_cache.len(), 6);
+}
+
+#[test]
+fn alibi_has_no_length_limit() {
+    let model = SimpleTransformer::from_config(&ModelConfig {
+        positional_encoding: PositionalEncodingKind::Alibi,
+        ..common::small_config()
+    })
+    .unwrap();
+    assert!(model.try_forward(&[3; 20]).is_ok());
+}
+
+#[test]
+fn alibi_slopes_match_the_paper() {
+    fn assert_slopes(n_heads: usize, expected: &[f32]) {
+        let slopes = alibi_slopes(n_heads);
+        assert_eq!(slopes.len(), expected.len());
+        for (s, e) in slopes.iter().zip(expected) {
+            assert!((s - e).abs() < 1e-7, "{n_heads} heads: {slopes:?}");
+        }
+    }
+    assert_slopes(8, &[2, 4, 8, 16, 32, 64, 128, 256].map(|d| 1.0 / d as f32));
+    // The 4-head slopes, then the odd slopes of 8 heads
+    assert_slopes(6, &[4, 16, 64, 256, 2, 8].map(|d| 1.0 / d as f32));
+}
+
+#[test]
+fn quantization_is_rejected() {
+    let config = ModelConfig {
+        use_quantization: true,
+        ..common::small_config()
+    };
+    assert_eq!(
+        SimpleTransformer::from_config(&config).err(),
+        Some(ConfigError::QuantizationUnsupported)
+    );
+}
+
+#[test]
+fn model_can_be_shared_across_threads() {
+    fn assert_shareable<T: Send + Sync + UnwindSafe + RefUnwindSafe>() {}
+    assert_shareable::<SimpleTransformer>();
+    assert_shareable::<llm_engine::inference::InferenceEngine>();
+}